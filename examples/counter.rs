@@ -25,9 +25,7 @@ impl MyAppLogic {
             println!("traversing into if_changed block");
             Column::new().build(cx, |cx| {
                 Label::new(format!("current count: {}", self.count)).build(cx);
-                if Button::new("Increment").build(cx) {
-                    self.count += 1;
-                }
+                self.count += Button::new("Increment").build_count(cx);
                 if self.count > 3 && self.count < 6 {
                     Padding::new().top(10.0).build(cx, |cx| {
                         let clicked = Clicked::new().build(cx, |cx| {
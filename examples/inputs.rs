@@ -0,0 +1,46 @@
+//! Numeric inputs and a switch, controlling one another.
+
+use druid::{AppLauncher, PlatformError, Widget, WindowDesc};
+
+use crochet::{AppHolder, Column, Cx, DruidAppData, Label, Row, Slider, Stepper, Switch};
+
+fn main() -> Result<(), PlatformError> {
+    let main_window = WindowDesc::new(ui_builder);
+    let data = Default::default();
+    AppLauncher::with_window(main_window)
+        .use_simple_logger()
+        .launch(data)
+}
+
+#[derive(Default)]
+struct MyAppLogic {
+    value: f64,
+    snap: bool,
+}
+
+impl MyAppLogic {
+    fn run(&mut self, cx: &mut Cx) {
+        Column::new().build(cx, |cx| {
+            Label::new(format!("value: {}", self.value)).build(cx);
+            let mut slider = Slider::new(self.value).range(0.0, 10.0);
+            if self.snap {
+                slider = slider.step(1.0);
+            }
+            self.value = slider.build(cx);
+            self.value = Stepper::new(self.value)
+                .range(0.0, 10.0)
+                .step(0.5)
+                .build(cx);
+            Row::new().build(cx, |cx| {
+                Label::new("Snap to whole numbers").build(cx);
+                self.snap = Switch::new(self.snap).build(cx);
+            });
+        });
+    }
+}
+
+fn ui_builder() -> impl Widget<DruidAppData> {
+    let mut app_logic = MyAppLogic::default();
+
+    AppHolder::new(move |cx| app_logic.run(cx))
+}
@@ -2,7 +2,9 @@
 
 use druid::{AppLauncher, PlatformError, Widget, WindowDesc};
 
-use crochet::{AppHolder, Button, Column, Cx, DruidAppData, Id, Label, List, ListData, Row};
+use crochet::{
+    AppHolder, Button, Column, Cx, DruidAppData, Id, Label, List, ListData, Row, Scroll,
+};
 
 fn main() -> Result<(), PlatformError> {
     let main_window = WindowDesc::new(ui_builder);
@@ -17,24 +19,30 @@ struct MyAppLogic {
     data: ListData<String>,
     list_view: List,
     counter: usize,
+    /// The item just created, to be scrolled to.
+    created: Option<Id>,
+    /// The view of the row to scroll to.
+    scroll_to: Option<Id>,
 }
 
 impl MyAppLogic {
     fn run(&mut self, cx: &mut Cx) {
         Column::new().build(cx, |cx| {
+            let has_selection = self.list_view.selected().is_some();
             Row::new().build(cx, |cx| {
                 if Button::new("Create").build(cx) {
                     self.data.push(format!("item {}", self.counter));
                     self.counter += 1;
+                    self.created = Some(self.data.stable_id_at_ix(self.data.len() - 1));
                 }
-                if Button::new("Delete").build(cx) {
+                if Button::new("Delete").disabled(!has_selection).build(cx) {
                     if let Some(id) = self.list_view.selected() {
                         if let Some(ix) = self.data.find_id(id) {
                             self.data.remove_at_ix(ix);
                         }
                     }
                 }
-                if Button::new("Update").build(cx) {
+                if Button::new("Update").disabled(!has_selection).build(cx) {
                     if let Some(id) = self.list_view.selected() {
                         if let Some(ix) = self.data.find_id(id) {
                             self.data.set_at_ix(ix, format!("update {}", self.counter));
@@ -44,8 +52,16 @@ impl MyAppLogic {
                 }
             });
             let mut new_sel = None;
-            self.list_view
-                .run(cx, &self.data, |cx, is_selected, id: Id, item| {
+            let created = self.created;
+            let mut created_row = None;
+            let mut scroll = Scroll::new().vertical();
+            if let Some(row) = self.scroll_to {
+                scroll = scroll.scroll_to(row);
+            }
+            let list_view = &mut self.list_view;
+            let data = &self.data;
+            scroll.build(cx, |cx| {
+                list_view.run(cx, data, |cx, is_selected, id: Id, item| {
                     Row::new().build(cx, |cx| {
                         if Button::new("Select").build(cx) {
                             new_sel = Some(id);
@@ -53,10 +69,20 @@ impl MyAppLogic {
                         let sel_str = if is_selected { "[*]" } else { "[ ]" };
                         Label::new(format!("{} {}", sel_str, item)).build(cx);
                     });
+                    if created == Some(id) {
+                        created_row = cx.last_id();
+                    }
                 });
+            });
             if let Some(id) = new_sel {
                 self.list_view.select(id);
             }
+            if let Some(row) = created_row {
+                // The row only exists now, so scroll to it in another run.
+                self.created = None;
+                self.scroll_to = Some(row);
+                cx.invalidator().invalidate();
+            }
         });
     }
 }
@@ -0,0 +1,60 @@
+//! App logic and rendering on separate threads, connected by the wire
+//! format.
+//!
+//! The two sides only exchange JSON strings, so the channels could just as
+//! well be a socket or a pipe to another process.
+
+use std::sync::mpsc;
+use std::thread;
+
+use druid::{AppLauncher, PlatformError, WindowDesc};
+
+use crochet::{Button, Column, Cx, Headless, Label, Renderer, WireAction};
+
+fn main() -> Result<(), PlatformError> {
+    let (to_app, from_renderer) = mpsc::channel::<String>();
+    let (to_renderer, from_app) = mpsc::channel::<String>();
+
+    // The app logic side.
+    thread::spawn(move || {
+        let mut app_logic = MyAppLogic::default();
+        let mut app = Headless::new(move |cx| app_logic.run(cx)).on_mutation(move |mutation| {
+            let json = serde_json::to_string(&mutation.to_wire()).unwrap();
+            let _ = to_renderer.send(json);
+        });
+        app.run();
+        for json in from_renderer {
+            let action: WireAction = serde_json::from_str(&json).unwrap();
+            app.remote_action(action);
+        }
+    });
+
+    // The rendering side.
+    let renderer = Renderer::new(move |action| {
+        let _ = to_app.send(serde_json::to_string(&action).unwrap());
+    });
+    let sender = renderer.mutation_sender();
+    thread::spawn(move || {
+        for json in from_app {
+            sender.send(serde_json::from_str(&json).unwrap());
+        }
+    });
+    let main_window = WindowDesc::new(move || renderer);
+    AppLauncher::with_window(main_window)
+        .use_simple_logger()
+        .launch(Default::default())
+}
+
+#[derive(Default)]
+struct MyAppLogic {
+    count: usize,
+}
+
+impl MyAppLogic {
+    fn run(&mut self, cx: &mut Cx) {
+        Column::new().build(cx, |cx| {
+            Label::new(format!("current count: {}", self.count)).build(cx);
+            self.count += Button::new("Increment").build_count(cx);
+        });
+    }
+}
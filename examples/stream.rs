@@ -0,0 +1,55 @@
+//! A simple example of subscribing to a stream.
+
+use std::time::Duration;
+
+use druid::{AppLauncher, PlatformError, Widget, WindowDesc};
+
+use crochet::{AppHolder, Button, Column, Cx, DruidAppData, Label};
+
+fn main() -> Result<(), PlatformError> {
+    let main_window = WindowDesc::new(ui_builder);
+    let data = Default::default();
+    AppLauncher::with_window(main_window)
+        .use_simple_logger()
+        .launch(data)
+}
+
+#[derive(Default)]
+struct MyAppLogic {
+    step: usize,
+}
+
+impl MyAppLogic {
+    fn run(&mut self, cx: &mut Cx) {
+        Column::new().build(cx, |cx| {
+            Label::new(format!("counting by {}", self.step + 1)).build(cx);
+            if Button::new("Faster").build(cx) {
+                self.step += 1;
+            }
+            // Changing the step restarts the ticker.
+            cx.use_stream(
+                &self.step,
+                |&step| {
+                    futures::stream::unfold(0, move |count| async move {
+                        async_std::task::sleep(Duration::from_secs(1)).await;
+                        Some((count, count + step + 1))
+                    })
+                },
+                |cx, latest| {
+                    let text = if let Some(count) = latest {
+                        format!("count: {}", count)
+                    } else {
+                        "waiting...".into()
+                    };
+                    Label::new(text).build(cx);
+                },
+            );
+        });
+    }
+}
+
+fn ui_builder() -> impl Widget<DruidAppData> {
+    let mut app_logic = MyAppLogic::default();
+
+    AppHolder::new(move |cx| app_logic.run(cx))
+}
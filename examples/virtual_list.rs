@@ -0,0 +1,61 @@
+//! A virtualized list of many rows, only building those in view.
+
+use druid::{AppLauncher, PlatformError, Widget, WindowDesc};
+
+use crochet::{AppHolder, Button, Cx, DruidAppData, Id, Label, List, ListData, Row};
+
+/// The height of a row of the list.
+const ROW_HEIGHT: f64 = 30.0;
+
+fn main() -> Result<(), PlatformError> {
+    let main_window = WindowDesc::new(ui_builder);
+    let data = Default::default();
+    AppLauncher::with_window(main_window)
+        .use_simple_logger()
+        .launch(data)
+}
+
+struct MyAppLogic {
+    data: ListData<String>,
+    list_view: List,
+}
+
+impl Default for MyAppLogic {
+    fn default() -> Self {
+        let mut data = ListData::default();
+        for i in 0..100_000 {
+            data.push(format!("log line {}", i));
+        }
+        MyAppLogic {
+            data,
+            list_view: List::default(),
+        }
+    }
+}
+
+impl MyAppLogic {
+    fn run(&mut self, cx: &mut Cx) {
+        // The list is the root, so the viewport is the size of the window.
+        let mut new_sel = None;
+        let data = &self.data;
+        self.list_view
+            .run_virtual(cx, data, ROW_HEIGHT, |cx, is_selected, id: Id, item| {
+                Row::new().build(cx, |cx| {
+                    if Button::new("Select").build(cx) {
+                        new_sel = Some(id);
+                    }
+                    let sel_str = if is_selected { "[*]" } else { "[ ]" };
+                    Label::new(format!("{} {}", sel_str, item)).build(cx);
+                });
+            });
+        if let Some(id) = new_sel {
+            self.list_view.select(id);
+        }
+    }
+}
+
+fn ui_builder() -> impl Widget<DruidAppData> {
+    let mut app_logic = MyAppLogic::default();
+
+    AppHolder::new(move |cx| app_logic.run(cx))
+}
@@ -0,0 +1,56 @@
+//! An app that opens and closes windows.
+
+use druid::PlatformError;
+
+use crochet::{Button, Column, Cx, Label, MultiWindowApp, Window};
+
+fn main() -> Result<(), PlatformError> {
+    let mut app_logic = MyAppLogic::default();
+    MultiWindowApp::new(move |cx| app_logic.run(cx))
+        .launcher()
+        .use_simple_logger()
+        .launch(Default::default())
+}
+
+#[derive(Default)]
+struct MyAppLogic {
+    main_closed: bool,
+    next_window: usize,
+    windows: Vec<usize>,
+}
+
+impl MyAppLogic {
+    fn run(&mut self, cx: &mut Cx) {
+        if !self.main_closed {
+            let mut open = false;
+            self.main_closed = Window::new("Windows").size((400.0, 300.0)).build(cx, |cx| {
+                Column::new().build(cx, |cx| {
+                    Label::new(format!("{} windows open", self.windows.len())).build(cx);
+                    open = Button::new("Open a window").build(cx);
+                });
+            });
+            if open {
+                self.windows.push(self.next_window);
+                self.next_window += 1;
+            }
+        }
+        let mut closed = Vec::new();
+        for &n in &self.windows {
+            cx.keyed(n, |cx| {
+                let mut close = false;
+                let user_closed = Window::new(format!("Window {}", n))
+                    .size((300.0, 200.0))
+                    .build(cx, |cx| {
+                        Column::new().build(cx, |cx| {
+                            Label::new(format!("This is window {}", n)).build(cx);
+                            close = Button::new("Close").build(cx);
+                        });
+                    });
+                if close || user_closed {
+                    closed.push(n);
+                }
+            });
+        }
+        self.windows.retain(|n| !closed.contains(n));
+    }
+}
@@ -1,22 +1,32 @@
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use druid::widget::prelude::*;
-use druid::widget::{Button, Click, ControllerHost, Label};
-use druid::Data;
+use druid::widget::Label;
+use druid::{Data, Rect, Vec2};
 
+use crate::invalidate::Wake;
 use crate::view;
 use crate::MutableWidget;
-use crate::{Id, MutIterItem, MutationIter, Payload};
+use crate::{Id, MutationIter, Payload};
 
 /// The type we use for app data for Druid integration.
 ///
-/// Currently this is action queues.
-///
-/// It should probably be a vec of actions, but we can refine
-/// later. For button clicks it doesn't matter.
+/// Currently this is action queues, one for each element with pending
+/// actions, in the order they were produced, and the elements explicitly
+/// invalidated since the last run.
 #[derive(Clone, Data, Default)]
-pub struct DruidAppData(Arc<HashMap<Id, Action>>);
+pub struct DruidAppData {
+    /// The queued actions, each with its sequence number, so that actions
+    /// of different elements can be taken in the order they were produced.
+    actions: Arc<HashMap<Id, Vec<(u64, Action)>>>,
+    next_seq: u64,
+    invalidated: Arc<HashSet<Id>>,
+    /// Whether the current run took an action after ending an element,
+    /// which may then be stale.
+    stale: bool,
+}
 
 /// Actions that can be produced by widgets,
 #[derive(Clone)]
@@ -25,6 +35,16 @@ pub enum Action {
     FutureResolved,
     TextChanged(String),
     Toggled(bool),
+    /// A new value for a numeric input, such as a slider.
+    ValueChanged(f64),
+    /// A new offset of a scroll container.
+    Scrolled(Vec2),
+    /// A new size of the viewport of a scroll container.
+    Resized(Size),
+    /// The user closed the window.
+    WindowClosed,
+    /// A user-defined action, retrieved by type.
+    Custom(Arc<dyn Any + Send + Sync>),
 }
 
 /// A widget that backs any render element in the crochet tree.
@@ -37,10 +57,6 @@ pub enum Action {
 pub enum AnyWidget {
     /// A normal widget.
     MutableWidget(Box<dyn MutableWidget>),
-    /// A do-nothing container for another widget.
-    ///
-    /// Currently we use this for state nodes.
-    Passthrough(Box<AnyWidget>),
 }
 
 impl MutableWidget for Label<DruidAppData> {
@@ -54,17 +70,10 @@ impl MutableWidget for Label<DruidAppData> {
     }
 }
 
-impl MutableWidget for ControllerHost<Button<DruidAppData>, Click<DruidAppData>> {
-    fn mutate(&mut self, _ctx: &mut EventCtx, _body: Option<&Payload>, _mut_iter: MutationIter) {
-        // TODO: Update button text here.
-    }
-}
-
 macro_rules! methods {
     ($method_name: ident, $self: ident, $($args:ident),+) => {
         match $self {
             AnyWidget::MutableWidget(w) => w.$method_name($($args),+),
-            AnyWidget::Passthrough(w) => w.$method_name($($args),+),
         }
     };
 }
@@ -115,15 +124,17 @@ impl AnyWidget {
         &mut self,
         ctx: &mut EventCtx,
         body: Option<&Payload>,
-        mut mut_iter: MutationIter,
+        mut_iter: MutationIter,
     ) {
         match self {
             AnyWidget::MutableWidget(p) => p.mutate(ctx, body, mut_iter),
-            AnyWidget::Passthrough(p) => {
-                if let Some(MutIterItem::Update(body, iter)) = mut_iter.next() {
-                    p.mutate_update(ctx, body, iter);
-                }
-            }
+        }
+    }
+
+    /// Locate the descendant widget of the view with the id.
+    pub(crate) fn locate(&self, id: Id) -> Option<Rect> {
+        match self {
+            AnyWidget::MutableWidget(w) => w.locate(id),
         }
     }
 
@@ -132,7 +143,7 @@ impl AnyWidget {
         ctx: &mut EventCtx,
         id: Id,
         body: &Payload,
-        mut mut_iter: MutationIter,
+        mut_iter: MutationIter,
     ) -> AnyWidget {
         match body {
             Payload::View(v) => {
@@ -140,39 +151,174 @@ impl AnyWidget {
                 widget.mutate_update(ctx, None, mut_iter);
                 widget
             }
-            Payload::State(_) | Payload::Future(..) | Payload::Placeholder => {
-                // Here we assume that the state node has exactly one
-                // child. Not awesome but it simplifies prototyping.
-                if let Some(MutIterItem::Insert(id, body, iter)) = mut_iter.next() {
-                    let child = Self::mutate_insert(ctx, id, body, iter);
-                    AnyWidget::Passthrough(Box::new(child))
-                } else {
-                    panic!("passthrough node expected child");
-                }
-            }
+            // Other nodes are flattened by the mutation iterator.
+            _ => panic!("only views have widgets"),
         }
     }
 }
 
 impl DruidAppData {
     pub(crate) fn queue_action(&mut self, id: Id, action: Action) {
-        Arc::make_mut(&mut self.0).insert(id, action);
+        Arc::make_mut(&mut self.actions)
+            .entry(id)
+            .or_default()
+            .push((self.next_seq, action));
+        self.next_seq += 1;
+    }
+
+    /// Take all queued actions for the id, in order.
+    pub(crate) fn take_actions(&mut self, id: Id) -> Vec<Action> {
+        if self.actions.contains_key(&id) {
+            Arc::make_mut(&mut self.actions)
+                .remove(&id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(_, action)| action)
+                .collect()
+        } else {
+            Vec::new()
+        }
     }
 
-    pub(crate) fn dequeue_action(&mut self, id: Id) -> Option<Action> {
-        if self.0.contains_key(&id) {
-            Arc::make_mut(&mut self.0).remove(&id)
+    /// Take all queued actions, in the order they were produced.
+    pub(crate) fn take_all_actions(&mut self) -> Vec<(Id, Action)> {
+        if self.actions.is_empty() {
+            return Vec::new();
+        }
+        let mut all: Vec<_> = std::mem::take(Arc::make_mut(&mut self.actions))
+            .into_iter()
+            .flat_map(|(id, actions)| {
+                actions
+                    .into_iter()
+                    .map(move |(seq, action)| (seq, id, action))
+            })
+            .collect();
+        all.sort_by_key(|(seq, _, _)| *seq);
+        all.into_iter()
+            .map(|(_, id, action)| (id, action))
+            .collect()
+    }
+
+    /// Take the queued actions for the id that match the predicate, in
+    /// order, leaving the others queued.
+    pub(crate) fn take_actions_where(
+        &mut self,
+        id: Id,
+        pred: impl Fn(&Action) -> bool,
+    ) -> Vec<Action> {
+        match self.actions.get(&id) {
+            // Only touch the queues when taking something, so that they
+            // still compare the same.
+            Some(queue) if queue.iter().any(|(_, action)| pred(action)) => (),
+            _ => return Vec::new(),
+        }
+        let map = Arc::make_mut(&mut self.actions);
+        let queue = map.get_mut(&id).unwrap();
+        let (taken, rest): (Vec<_>, Vec<_>) = queue.drain(..).partition(|(_, action)| pred(action));
+        if rest.is_empty() {
+            map.remove(&id);
         } else {
-            None
+            *queue = rest;
         }
+        taken.into_iter().map(|(_, action)| action).collect()
     }
 
-    /// Report whether the id has a non-empty action queue.
+    /// Take the queued user-defined actions of type `T` for the id, in
+    /// order, leaving other actions queued.
+    pub(crate) fn take_custom<T: Any + Send + Sync>(&mut self, id: Id) -> Vec<Arc<T>> {
+        self.take_actions_where(
+            id,
+            |action| matches!(action, Action::Custom(a) if a.is::<T>()),
+        )
+        .into_iter()
+        .filter_map(|action| match action {
+            Action::Custom(a) => a.downcast().ok(),
+            _ => None,
+        })
+        .collect()
+    }
+
+    /// Queue a user-defined action for the element with this id.
+    ///
+    /// Custom widgets use this to deliver events to the app logic, which
+    /// retrieves them by type with `Cx::take_custom_actions`.
+    pub fn queue_custom<T: Any + Send + Sync>(&mut self, id: Id, action: T) {
+        self.queue_action(id, Action::Custom(Arc::new(action)));
+    }
+
+    /// Apply a wake request from an `Invalidator`.
+    pub(crate) fn wake(&mut self, wake: Wake) {
+        match wake {
+            Wake::Run => (),
+            Wake::Node(id) => self.invalidate(id),
+            Wake::Resolved(id) => self.queue_action(id, Action::FutureResolved),
+        }
+    }
+
+    /// Mark the element as invalidated, until the next run.
+    pub(crate) fn invalidate(&mut self, id: Id) {
+        Arc::make_mut(&mut self.invalidated).insert(id);
+    }
+
+    /// Note that the app logic took an action after ending an element, so
+    /// that element may not reflect the action.
+    pub(crate) fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    /// Report whether the run of the app logic just finished needs another
+    /// run to bring the tree up to date.
+    ///
+    /// That is the case when it took an action after ending an element,
+    /// as the action may change state the element was built from.
+    /// Otherwise, every element was built after the actions it could
+    /// depend on were taken.
+    pub(crate) fn has_pending(&self) -> bool {
+        self.stale
+    }
+
+    /// Clear what the run of the app logic just finished has seen: the
+    /// invalidated elements, and whether it left the tree stale.
+    pub(crate) fn end_run(&mut self) {
+        if !self.invalidated.is_empty() {
+            self.invalidated = Default::default();
+        }
+        self.stale = false;
+    }
+
+    /// Report whether the id has a non-empty action queue, or was
+    /// invalidated.
     pub(crate) fn has_action(&self, id: Id) -> bool {
-        self.0.contains_key(&id)
+        self.actions.contains_key(&id) || self.invalidated.contains(&id)
     }
+}
 
-    pub(crate) fn has_any_action(&self) -> bool {
-        !self.0.is_empty()
+#[cfg(test)]
+mod tests {
+    use super::{Action, DruidAppData};
+    use crate::Id;
+
+    #[test]
+    fn take_all_actions_keeps_the_order_produced() {
+        let ids: Vec<Id> = (0..8).map(|_| Id::new()).collect();
+        let mut data = DruidAppData::default();
+        for (i, id) in ids.iter().enumerate().rev() {
+            data.queue_action(*id, Action::ValueChanged(i as f64));
+            data.queue_action(ids[0], Action::ValueChanged(-(i as f64)));
+        }
+        let taken: Vec<(Id, f64)> = data
+            .take_all_actions()
+            .into_iter()
+            .map(|(id, action)| match action {
+                Action::ValueChanged(value) => (id, value),
+                _ => unreachable!(),
+            })
+            .collect();
+        let expected: Vec<(Id, f64)> = (0..8)
+            .rev()
+            .flat_map(|i| vec![(ids[i], i as f64), (ids[0], -(i as f64))])
+            .collect();
+        assert_eq!(taken, expected);
+        assert!(data.take_all_actions().is_empty());
     }
 }
@@ -1,18 +1,16 @@
 //! A Druid widget that contains the application.
 
-use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use druid::widget::prelude::*;
-use druid::{Point, Selector, SingleUse, WidgetPod};
+use druid::{ExtEventSink, Point, WidgetPod};
 
-use crate::state::State;
+use crate::invalidate::{Invalidator, WAKE};
 use crate::{
-    any_widget::{Action, AnyWidget, DruidAppData},
+    any_widget::{AnyWidget, DruidAppData},
     widget,
 };
-use crate::{Cx, Id, MutationIter, Tree};
-
-pub const ASYNC: Selector<SingleUse<(Id, Id, Box<dyn State>)>> = Selector::new("crochet.async");
+use crate::{Cx, MutationIter, Spawner, Tree};
 
 /// A container for a user application.
 ///
@@ -24,8 +22,8 @@ pub const ASYNC: Selector<SingleUse<(Id, Id, Box<dyn State>)>> = Selector::new("
 /// architecture, that is stored in the app logic closure and the
 /// Crochet tree instead.
 ///
-/// Right now I'm only thinking about the single window case. For
-/// multi-window, this should probably be an app delegate.
+/// This is for the single window case; apps with several windows use
+/// `MultiWindowApp` instead.
 pub struct AppHolder {
     tree: Tree,
     /// The app logic.
@@ -34,36 +32,61 @@ pub struct AppHolder {
     /// favor is simpler types and less monomorphization.
     app_logic: Box<dyn FnMut(&mut Cx)>,
     child: WidgetPod<DruidAppData, AnyWidget>,
-
-    /// This is where the values of resolved futures are stored. It's
-    /// not ideal, as they are not garbage collected when the Id is
-    /// removed from the tree. A better idea is probably to store them
-    /// in the tree, but that involves more ceremony, especially around
-    /// ownership.
-    resolved_futures: HashMap<Id, Box<dyn State>>,
+    /// The executor for futures spawned by the app logic.
+    spawner: Option<Box<dyn Spawner>>,
+    /// The sink for wake requests, once the window is connected.
+    sink: Arc<Mutex<Option<ExtEventSink>>>,
+    invalidator: Invalidator,
+    /// Whether the app logic needs to run at the next animation frame.
+    ///
+    /// This is set when actions are queued, so that any number of events
+    /// within a frame cause a single run.
+    dirty: bool,
 }
 
 impl AppHolder {
     pub fn new(app_logic: impl FnMut(&mut Cx) + 'static) -> AppHolder {
         let root = AnyWidget::MutableWidget(Box::new(widget::SizedBox::new(&Default::default())));
         let child = WidgetPod::new(root);
+        let sink: Arc<Mutex<Option<ExtEventSink>>> = Default::default();
+        let invalidator = Invalidator::for_sink(sink.clone());
         AppHolder {
             tree: Tree::default(),
             app_logic: Box::new(app_logic),
             child,
-            resolved_futures: Default::default(),
+            spawner: default_spawner(),
+            sink,
+            invalidator,
+            dirty: true,
         }
     }
 
+    /// Set the executor for futures spawned by the app logic.
+    ///
+    /// When the `async-std` feature is enabled, the default is to spawn
+    /// on async-std; otherwise there is no default, and `use_future`
+    /// needs a spawner to be set.
+    pub fn with_spawner(mut self, spawner: impl Spawner + 'static) -> AppHolder {
+        self.spawner = Some(Box::new(spawner));
+        self
+    }
+
+    /// A handle for scheduling runs of the app logic from outside it.
+    pub fn invalidator(&self) -> Invalidator {
+        self.invalidator.clone()
+    }
+
     /// Run the app logic, mutating the tree.
     ///
     /// We cheat slightly, only implementing this for event contexts.
     /// This is probably good enough for a prototype, but will probably
     /// need more care for a real integration.
     fn run_app_logic(&mut self, ctx: &mut EventCtx, data: &mut DruidAppData) {
-        let needs_update = data.has_any_action();
-        let event_sink = ctx.get_external_handle();
-        let mut cx = Cx::new(&self.tree, data, &self.resolved_futures, &event_sink);
+        self.sink
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| ctx.get_external_handle());
+        let mut cx = Cx::new(&self.tree, data, &self.invalidator, self.spawner.as_deref());
         (self.app_logic)(&mut cx);
         let mutation = cx.into_mutation();
         let mut_iter = MutationIter::new(&self.tree, &mutation);
@@ -71,26 +94,48 @@ impl AppHolder {
             child.mutate_update(ctx, None, mut_iter);
         });
         self.tree.mutate(mutation);
-        // This will bring the ui up-to-date and avoid stale state.
-        // A better solution would be nice, but this is simple and seems to work.
-        if needs_update {
-            ctx.request_timer(std::time::Duration::from_secs(0));
+        // If the app logic took an action after ending some elements, they
+        // may be stale, so run once more on the next frame.
+        self.dirty = data.has_pending();
+        if self.dirty {
+            ctx.request_anim_frame();
         }
+        data.end_run();
     }
 }
 
+#[cfg(feature = "async-std")]
+pub(crate) fn default_spawner() -> Option<Box<dyn Spawner>> {
+    Some(Box::new(crate::spawner::AsyncStdSpawner))
+}
+
+#[cfg(not(feature = "async-std"))]
+pub(crate) fn default_spawner() -> Option<Box<dyn Spawner>> {
+    None
+}
+
 impl Widget<DruidAppData> for AppHolder {
     fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
         if let Event::Command(cmd) = event {
-            if let Some(payload) = cmd.get(ASYNC) {
-                if let Some((id, f_id, val)) = payload.take() {
-                    self.resolved_futures.insert(f_id, val);
-                    data.queue_action(id, Action::FutureResolved);
-                }
+            if let Some(wake) = cmd.get(WAKE) {
+                data.wake(*wake);
+                self.dirty = true;
             }
         }
+        let before = data.clone();
         self.child.event(ctx, event, data, env);
-        self.run_app_logic(ctx, data);
+        if !before.same(data) {
+            // A widget queued an action.
+            self.dirty = true;
+        }
+        if self.dirty {
+            match event {
+                // The first run builds the initial tree, before the window
+                // is painted.
+                Event::WindowConnected | Event::AnimFrame(_) => self.run_app_logic(ctx, data),
+                _ => ctx.request_anim_frame(),
+            }
+        }
     }
 
     fn lifecycle(
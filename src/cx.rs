@@ -1,46 +1,44 @@
 //! The main Crochet interface.
 
-use std::collections::HashMap;
+use std::any::Any;
+use std::fmt::Debug;
+use std::future::Future;
+use std::hash::Hash;
 use std::panic::Location;
+use std::sync::Arc;
 
-// The unused annotations are mostly for optional async.
-#[allow(unused)]
-use druid::{ExtEventSink, SingleUse, Target};
+use futures::future::{abortable, AbortHandle, Abortable};
+use futures::{Stream, StreamExt};
 
-#[cfg(feature = "async-std")]
-use async_std::future::Future;
-
-use crate::any_widget::DruidAppData;
-#[cfg(feature = "async-std")]
-use crate::app_holder::ASYNC;
+use crate::any_widget::{Action, DruidAppData};
 use crate::id::Id;
-use crate::state::State;
+use crate::invalidate::Invalidator;
+use crate::key::{Key, UserKey};
+use crate::spawner::Spawner;
+use crate::state::{FutureCell, Memo, State, StateCell};
 use crate::tree::{MutCursor, Mutation, Payload, Tree};
 use crate::view::View;
 
 pub struct Cx<'a> {
     mut_cursor: MutCursor<'a>,
     pub(crate) app_data: &'a mut DruidAppData,
-    #[allow(unused)]
-    resolved_futures: &'a HashMap<Id, Box<dyn State>>,
-    #[allow(unused)]
-    event_sink: &'a ExtEventSink,
+    invalidator: &'a Invalidator,
+    spawner: Option<&'a dyn Spawner>,
 }
 
 impl<'a> Cx<'a> {
-    /// Only public for experimentation.
-    pub fn new(
+    pub(crate) fn new(
         tree: &'a Tree,
         app_data: &'a mut DruidAppData,
-        resolved_futures: &'a HashMap<Id, Box<dyn State>>,
-        event_sink: &'a ExtEventSink,
+        invalidator: &'a Invalidator,
+        spawner: Option<&'a dyn Spawner>,
     ) -> Cx<'a> {
         let mut_cursor = MutCursor::new(tree);
         Cx {
             mut_cursor,
             app_data,
-            resolved_futures,
-            event_sink,
+            invalidator,
+            spawner,
         }
     }
 
@@ -52,6 +50,15 @@ impl<'a> Cx<'a> {
         self.mut_cursor.end();
     }
 
+    /// The id of the element most recently ended, such as the view just
+    /// built.
+    ///
+    /// This is how app logic learns the id of a view, for example to
+    /// scroll to it.
+    pub fn last_id(&self) -> Option<Id> {
+        self.mut_cursor.last_id()
+    }
+
     /// Add a view as a leaf.
     ///
     /// This method is expected to be called mostly by the `build`
@@ -79,9 +86,6 @@ impl<'a> Cx<'a> {
 
     /// Traverse into a subtree only if the data has changed.
     ///
-    /// The supplied callback *must* create only one widget. This is not
-    /// enforced, and will probably be relaxed one way or other.
-    ///
     /// This method also traverses into the subtree if any of its action
     /// queues are non-empty.
     #[track_caller]
@@ -113,15 +117,156 @@ impl<'a> Cx<'a> {
         let result = if changed || actions {
             Some(f(self))
         } else {
-            // TODO: here's a place that needs work if we relax the requirement
-            // of exactly one child node.
-            self.mut_cursor.skip_one();
+            self.mut_cursor.skip_rest();
             None
         };
         self.mut_cursor.end();
         result
     }
 
+    /// Group elements without creating a widget for the group.
+    ///
+    /// The widgets created by the callback are contributed directly to the
+    /// nearest enclosing view, so a component can add several children
+    /// to a `Row` or `Column`.
+    #[track_caller]
+    pub fn fragment<T>(&mut self, f: impl FnOnce(&mut Cx) -> T) -> T {
+        self.mut_cursor
+            .begin_loc(Payload::Fragment, Location::caller());
+        let result = f(self);
+        self.mut_cursor.end();
+        result
+    }
+
+    /// Traverse into a subtree identified by a user-provided key.
+    ///
+    /// The key is combined with the call site, so that elements emitted
+    /// from a loop are matched by key rather than by sequence number. This
+    /// way, their identity (including widget and memoized state) survives
+    /// when siblings are inserted or removed. Keys should be unique among
+    /// the siblings emitted from one call site.
+    #[track_caller]
+    pub fn keyed<K, T>(&mut self, key: K, f: impl FnOnce(&mut Cx) -> T) -> T
+    where
+        K: Hash + Eq + Debug + Send + Sync + 'static,
+    {
+        let key = self
+            .mut_cursor
+            .key_from_loc_user(Location::caller(), UserKey::new(key));
+        self.begin_keyed(key);
+        let result = f(self);
+        self.mut_cursor.end();
+        result
+    }
+
+    /// Traverse a keyed subtree for each item, in order.
+    ///
+    /// This is `keyed` in a loop, with the key of each item given by
+    /// `key`. As all the keys are known up front, subtrees that were
+    /// reordered are matched with as few moves as possible: those still in
+    /// order stay in place, and only the others are moved. For example,
+    /// moving the first item to the end moves only that item.
+    #[track_caller]
+    pub fn keyed_each<I, K>(
+        &mut self,
+        items: impl IntoIterator<Item = I>,
+        key: impl Fn(&I) -> K,
+        mut f: impl FnMut(&mut Cx, I),
+    ) where
+        K: Hash + Eq + Debug + Send + Sync + 'static,
+    {
+        let loc = Location::caller();
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        for item in items {
+            let user = UserKey::new(key(&item));
+            keys.push(self.mut_cursor.key_from_loc_user(loc, user));
+            values.push(item);
+        }
+        self.mut_cursor.expect_keys(&keys);
+        for (key, item) in keys.into_iter().zip(values) {
+            self.begin_keyed(key);
+            f(self, item);
+            self.mut_cursor.end();
+        }
+    }
+
+    fn begin_keyed(&mut self, key: Key) {
+        self.mut_cursor.begin_core(key, |_id, old_body| {
+            if let Some(Payload::Placeholder) = old_body {
+                (None, ())
+            } else {
+                (Some(Payload::Placeholder), ())
+            }
+        });
+    }
+
+    /// Keep local state in the tree.
+    ///
+    /// When this element is first inserted, the state is created with
+    /// `init`. It then persists for as long as the element is present in
+    /// the tree, and is dropped when the element is deleted. The callback
+    /// can mutate the state in place; changes are seen by later runs.
+    #[track_caller]
+    pub fn use_state<T: Send + 'static, U>(
+        &mut self,
+        init: impl FnOnce() -> T,
+        f: impl FnOnce(&mut Cx, &mut T) -> U,
+    ) -> U {
+        let key = self.mut_cursor.key_from_loc(Location::caller());
+        let cell = self.mut_cursor.begin_core(key, |_id, old_body| {
+            if let Some(Payload::State(old_state)) = old_body {
+                if let Some(cell) = old_state.as_any().downcast_ref::<StateCell<T>>() {
+                    return (None, cell.clone());
+                }
+            }
+            // Inserting new state (or the type changed, which shouldn't happen)
+            let cell = StateCell::new(init());
+            (Some(Payload::State(Box::new(cell.clone()))), cell)
+        });
+        let result = f(self, &mut cell.lock());
+        self.mut_cursor.end();
+        result
+    }
+
+    /// Memoize a computation.
+    ///
+    /// When the dependencies change (including first insert), call
+    /// `compute` and store its result in the tree along with the
+    /// dependencies. Otherwise, return the stored result. This is
+    /// independent of whether any surrounding subtree is skipped.
+    ///
+    /// The result is returned as a shared reference, so it can be held
+    /// while the context is used to build more of the tree.
+    #[track_caller]
+    pub fn use_memo<T, R>(&mut self, deps: &T, compute: impl FnOnce() -> R) -> Arc<R>
+    where
+        T: State + PartialEq + Clone + 'static,
+        R: Send + Sync + 'static,
+    {
+        let key = self.mut_cursor.key_from_loc(Location::caller());
+        let result = self.mut_cursor.begin_core(key, |_id, old_body| {
+            if let Some(Payload::State(old_state)) = old_body {
+                if let Some(memo) = old_state.as_any().downcast_ref::<Memo>() {
+                    if memo.deps.eq(deps) {
+                        if let Ok(result) = memo.result.clone().downcast::<R>() {
+                            return (None, result);
+                        }
+                    }
+                }
+            }
+            // Dependencies changed, or inserting new memo
+            let result = Arc::new(compute());
+            let memo = Memo {
+                deps: Box::new(deps.clone()),
+                result: result.clone(),
+            };
+            (Some(Payload::State(Box::new(memo))), result)
+        });
+        self.mut_cursor.end();
+        result
+    }
+
     /// Spawn a future when the data changes.
     ///
     /// When the data changes (including first insert), call `future_cb` and
@@ -129,8 +274,12 @@ impl<'a> Cx<'a> {
     /// the data into the async callback, it should clone it first.
     ///
     /// The value of the future is then made available to the main body
-    /// callback.
-    #[cfg(feature = "async-std")]
+    /// callback. It is stored with the element, so it is dropped when the
+    /// element is deleted. A future still running at that point, or when
+    /// the data changes, is cancelled.
+    ///
+    /// The future is run by the spawner of the `AppHolder`; it is an error
+    /// to call this method if there is none.
     #[track_caller]
     pub fn use_future<T, U, V, F, FC>(
         &mut self,
@@ -146,61 +295,133 @@ impl<'a> Cx<'a> {
         F: Future<Output = U> + Send + 'static,
     {
         let key = self.mut_cursor.key_from_loc(Location::caller());
-        let (id, f_id, changed) = self.mut_cursor.begin_core(key, |id, old_body| {
-            if let Some(Payload::Future(f_id, old_data)) = old_body {
-                if let Some(old_data) = old_data.as_any().downcast_ref::<T>() {
-                    if old_data == data {
-                        (None, (id, *f_id, false))
-                    } else {
-                        // Types match, data not equal
-                        let f_id = Id::new();
-                        (
-                            Some(Payload::Future(f_id, Box::new(data.clone()))),
-                            (id, f_id, true),
-                        )
+        let (id, result, future) = self.mut_cursor.begin_core(key, |id, old_body| {
+            if let Some(Payload::Future(old_state)) = old_body {
+                if let Some(cell) = old_state.as_any().downcast_ref::<FutureCell>() {
+                    if cell.data.eq(data) {
+                        return (None, (id, cell.result.clone(), None));
                     }
-                } else {
-                    // Downcast failed; this shouldn't happen
-                    let f_id = Id::new();
-                    (
-                        Some(Payload::Future(f_id, Box::new(data.clone()))),
-                        (id, f_id, true),
-                    )
                 }
-            } else {
-                // Probably inserting new state
-                let f_id = Id::new();
-                (
-                    Some(Payload::Future(f_id, Box::new(data.clone()))),
-                    (id, f_id, true),
-                )
             }
+            // Data changed, or inserting new future. Replacing the old
+            // cell cancels its task.
+            let (future, abort) = abortable(future_cb(data));
+            let cell = FutureCell {
+                data: Box::new(data.clone()),
+                result: Default::default(),
+                abort,
+            };
+            let result = cell.result.clone();
+            (
+                Some(Payload::Future(Box::new(cell))),
+                (id, result, Some(future)),
+            )
         });
-        if changed {
+        if let Some(future) = future {
             // Spawn the future.
-            let future = future_cb(data);
-            let sink = self.event_sink.clone();
+            let invalidator = self.invalidator.clone();
+            let slot = result.clone();
             let boxed_future = Box::pin(async move {
-                let result = future.await;
-                let boxed_result: Box<dyn State> = Box::new(result);
-                let payload = (id, f_id, boxed_result);
-                if let Err(e) = sink.submit_command(ASYNC, SingleUse::new(payload), Target::Auto) {
-                    println!("error {:?} submitting", e);
+                // If aborted, the element is gone or has a newer future.
+                if let Ok(val) = future.await {
+                    let boxed_result: Box<dyn State> = Box::new(val);
+                    *slot.lock().unwrap() = Some(boxed_result);
+                    invalidator.resolve(id);
                 }
             });
-            async_std::task::spawn(boxed_future);
+            self.spawner
+                .expect("use_future requires a spawner; see AppHolder::with_spawner")
+                .spawn(boxed_future);
         }
-        // Remove the "FutureResolved" action if it was sent.
-        let _ = self.app_data.dequeue_action(id);
-        let future_result = self
-            .resolved_futures
-            .get(&f_id)
+        // Remove the "FutureResolved" actions if they were sent.
+        let _ = self
+            .app_data
+            .take_actions_where(id, |action| matches!(action, Action::FutureResolved));
+        let future_result = result.lock().unwrap();
+        let future_result = future_result
+            .as_ref()
             .and_then(|result| result.as_any().downcast_ref());
         let result = f(self, future_result);
         self.mut_cursor.end();
         result
     }
 
+    /// Subscribe to a stream when the dependencies change.
+    ///
+    /// When the dependencies change (including first insert), call
+    /// `make_stream` and spawn a task forwarding its items. Each item
+    /// re-runs the app logic, and the latest item is made available to
+    /// the main body callback.
+    ///
+    /// As with `use_future`, the latest item is stored with the element,
+    /// and the task is cancelled when the element is deleted or the
+    /// dependencies change.
+    #[track_caller]
+    pub fn use_stream<T, U, V, S, SC>(
+        &mut self,
+        deps: &T,
+        make_stream: SC,
+        f: impl FnOnce(&mut Cx, Option<&U>) -> V,
+    ) -> V
+    where
+        T: State + PartialEq + Clone + Sync + 'static,
+        SC: FnOnce(&T) -> S,
+        U: Send + State + 'static,
+        S: Stream<Item = U> + Send + 'static,
+    {
+        let key = self.mut_cursor.key_from_loc(Location::caller());
+        let (id, latest, stream) = self.mut_cursor.begin_core(key, |id, old_body| {
+            if let Some(Payload::Future(old_state)) = old_body {
+                if let Some(cell) = old_state.as_any().downcast_ref::<FutureCell>() {
+                    if cell.data.eq(deps) {
+                        return (None, (id, cell.result.clone(), None));
+                    }
+                }
+            }
+            // Dependencies changed, or subscribing for the first time.
+            // Replacing the old cell cancels its task.
+            let (abort, registration) = AbortHandle::new_pair();
+            let cell = FutureCell {
+                data: Box::new(deps.clone()),
+                result: Default::default(),
+                abort,
+            };
+            let latest = cell.result.clone();
+            (
+                Some(Payload::Future(Box::new(cell))),
+                (id, latest, Some((make_stream(deps), registration))),
+            )
+        });
+        if let Some((stream, registration)) = stream {
+            // Spawn a task forwarding the stream.
+            let invalidator = self.invalidator.clone();
+            let slot = latest.clone();
+            let forward = async move {
+                let mut stream = Box::pin(stream);
+                while let Some(val) = stream.next().await {
+                    let boxed_val: Box<dyn State> = Box::new(val);
+                    *slot.lock().unwrap() = Some(boxed_val);
+                    invalidator.resolve(id);
+                }
+            };
+            let boxed_future = Box::pin(async move {
+                let _ = Abortable::new(forward, registration).await;
+            });
+            self.spawner
+                .expect("use_stream requires a spawner; see AppHolder::with_spawner")
+                .spawn(boxed_future);
+        }
+        // Remove the "FutureResolved" actions if they were sent.
+        let _ = self
+            .app_data
+            .take_actions_where(id, |action| matches!(action, Action::FutureResolved));
+        let latest = latest.lock().unwrap();
+        let latest = latest.as_ref().and_then(|val| val.as_any().downcast_ref());
+        let result = f(self, latest);
+        self.mut_cursor.end();
+        result
+    }
+
     /// A low-level method to skip nodes.
     ///
     /// There must be `n` nodes in the tree to skip.
@@ -219,6 +440,11 @@ impl<'a> Cx<'a> {
         }
     }
 
+    /// A low-level method to skip the remaining nodes of the current node.
+    pub fn skip_rest(&mut self) {
+        self.mut_cursor.skip_rest();
+    }
+
     /// A low-level method to insert a subtree.
     pub fn begin_insert(&mut self) {
         self.mut_cursor.begin_insert(Payload::Placeholder);
@@ -231,8 +457,76 @@ impl<'a> Cx<'a> {
         self.mut_cursor.begin_update(Payload::Placeholder);
     }
 
+    /// A handle for scheduling runs of the app logic from outside it.
+    pub fn invalidator(&self) -> Invalidator {
+        self.invalidator.clone()
+    }
+
+    /// Take the actions queued for the element, in the order they were
+    /// produced.
+    ///
+    /// Actions accumulate until taken, so there may be several between
+    /// runs of the app logic, for example from fast clicks on a button.
+    pub fn take_actions(&mut self, id: Id) -> Vec<Action> {
+        let actions = self.app_data.take_actions(id);
+        self.note_taken(None, !actions.is_empty());
+        actions
+    }
+
+    /// Take the queued actions for the element that match the predicate,
+    /// in the order they were produced.
+    ///
+    /// Other actions are left in the queue. Views use this to take only
+    /// the actions their widget produces, so that user-defined actions
+    /// queued for the same element are not lost.
+    pub fn take_actions_where(&mut self, id: Id, pred: impl Fn(&Action) -> bool) -> Vec<Action> {
+        let actions = self.app_data.take_actions_where(id, pred);
+        self.note_taken(None, !actions.is_empty());
+        actions
+    }
+
+    /// Take the queued user-defined actions of type `T` for the element,
+    /// in the order they were produced.
+    ///
+    /// Actions of other types are left in the queue.
+    pub fn take_custom_actions<T: Any + Send + Sync>(&mut self, id: Id) -> Vec<Arc<T>> {
+        let actions = self.app_data.take_custom(id);
+        self.note_taken(None, !actions.is_empty());
+        actions
+    }
+
+    /// Take the queued actions for a view that match the predicate, where
+    /// the view was not built from them, as for the clicks of a button.
+    ///
+    /// Unlike `take_actions_where`, the view itself is not made stale by
+    /// taking them, so a view built first needs no second run.
+    pub(crate) fn take_events_where(
+        &mut self,
+        id: Id,
+        pred: impl Fn(&Action) -> bool,
+    ) -> Vec<Action> {
+        let actions = self.app_data.take_actions_where(id, pred);
+        self.note_taken(Some(id), !actions.is_empty());
+        actions
+    }
+
+    /// Elements ended before an action is taken were built without it, so
+    /// the driver runs the app logic again to update them, apart from the
+    /// view taking its events, if it was ended first.
+    fn note_taken(&mut self, view: Option<Id>, taken: bool) {
+        let first = self.mut_cursor.first_id();
+        if taken && first.is_some() && first != view {
+            self.app_data.mark_stale();
+        }
+    }
+
     /// Report whether the current element has an action.
     ///
+    /// This covers all the children of the current element that have not
+    /// been visited yet, and their descendants, so it is usually called
+    /// right after beginning the element, to decide whether its subtree
+    /// can be skipped.
+    ///
     /// For the future, this should probably change to an `Option<usize>`,
     /// reporting how many elements to skip before the next action.
     pub fn has_action(&self) -> bool {
@@ -241,3 +535,31 @@ impl<'a> Cx<'a> {
             .any(|id| self.app_data.has_action(id))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::{Button, Headless, Label};
+
+    #[test]
+    fn if_changed_sees_actions_of_later_children() {
+        let clicks = Rc::new(Cell::new(0));
+        let counted = clicks.clone();
+        let mut app = Headless::new(move |cx| {
+            cx.if_changed((), |cx| {
+                Label::new("first").build(cx);
+                if Button::new("second").build(cx) {
+                    counted.set(counted.get() + 1);
+                }
+            });
+        });
+        app.run();
+        let button = app.find(|b: &Button| b.text() == "second").unwrap();
+        app.click(button);
+        assert_eq!(clicks.get(), 1);
+        app.click(button);
+        assert_eq!(clicks.get(), 2);
+    }
+}
@@ -0,0 +1,412 @@
+//! Running app logic without a window.
+
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+
+use druid::{Size, Vec2};
+
+use crate::any_widget::{Action, DruidAppData};
+use crate::app_holder::default_spawner;
+use crate::invalidate::{Invalidator, Wake};
+use crate::wire::WireAction;
+use crate::{Cx, Id, Mutation, Payload, Spawner, Tree};
+
+/// A driver for app logic without a Druid window.
+///
+/// Like `AppHolder`, it owns the tree and the action queues, and runs the
+/// app logic to produce and apply mutations. But there are no widgets:
+/// instead, tests inject the actions widgets would produce, and query the
+/// resulting tree.
+///
+/// ```ignore
+/// let mut app = Headless::new(move |cx| app_logic.run(cx));
+/// app.run();
+/// let button = app.find(|b: &Button| b.text() == "Increment").unwrap();
+/// app.click(button);
+/// assert!(app.find(|l: &Label| l.text() == "current count: 1").is_some());
+/// ```
+pub struct Headless {
+    tree: Tree,
+    app_data: DruidAppData,
+    app_logic: Box<dyn FnMut(&mut Cx)>,
+    spawner: Option<Box<dyn Spawner>>,
+    /// Called with each mutation, before it is applied.
+    on_mutation: Option<Box<dyn FnMut(&Mutation)>>,
+    /// Wake requests from invalidators since the last run.
+    wakes: Arc<Mutex<Vec<Wake>>>,
+    invalidator: Invalidator,
+}
+
+impl Headless {
+    /// Create a driver for the app logic.
+    ///
+    /// As in `AppHolder`, futures are spawned on async-std when the
+    /// `async-std` feature is enabled. Otherwise, there is no spawner until
+    /// one is set with `with_spawner`, and `use_future` and `use_stream`
+    /// panic.
+    pub fn new(app_logic: impl FnMut(&mut Cx) + 'static) -> Headless {
+        let wakes: Arc<Mutex<Vec<Wake>>> = Default::default();
+        let pushed = wakes.clone();
+        let invalidator = Invalidator::new(move |wake| pushed.lock().unwrap().push(wake));
+        Headless {
+            tree: Tree::default(),
+            app_data: Default::default(),
+            app_logic: Box::new(app_logic),
+            spawner: default_spawner(),
+            on_mutation: None,
+            wakes,
+            invalidator,
+        }
+    }
+
+    /// Set the executor for futures spawned by the app logic.
+    ///
+    /// A single-threaded executor lets tests decide when futures make
+    /// progress; results are delivered on the next run.
+    pub fn with_spawner(mut self, spawner: impl Spawner + 'static) -> Headless {
+        self.spawner = Some(Box::new(spawner));
+        self
+    }
+
+    /// Observe each mutation produced by the app logic.
+    ///
+    /// This is how app logic drives a remote `Renderer`: the mutations are
+    /// sent with `Mutation::to_wire`, and actions coming back are delivered
+    /// with `remote_action`.
+    pub fn on_mutation(mut self, f: impl FnMut(&Mutation) + 'static) -> Headless {
+        self.on_mutation = Some(Box::new(f));
+        self
+    }
+
+    /// A handle for scheduling runs of the app logic from outside it.
+    ///
+    /// Nothing runs by itself: wake requests are picked up by the next
+    /// call to `run`.
+    pub fn invalidator(&self) -> Invalidator {
+        self.invalidator.clone()
+    }
+
+    /// Run the app logic, applying the resulting mutation to the tree.
+    ///
+    /// As in `AppHolder`, if the app logic took an action after ending
+    /// some elements, it is run a second time to bring them up to date.
+    pub fn run(&mut self) {
+        for wake in self.wakes.lock().unwrap().drain(..) {
+            self.app_data.wake(wake);
+        }
+        self.run_once();
+        if self.app_data.has_pending() {
+            self.app_data.end_run();
+            self.run_once();
+        }
+        self.app_data.end_run();
+    }
+
+    fn run_once(&mut self) {
+        let spawner = self.spawner.as_deref();
+        let mut cx = Cx::new(&self.tree, &mut self.app_data, &self.invalidator, spawner);
+        (self.app_logic)(&mut cx);
+        let mutation = cx.into_mutation();
+        if let Some(on_mutation) = &mut self.on_mutation {
+            on_mutation(&mutation);
+        }
+        self.tree.mutate(mutation);
+    }
+
+    /// Click the element, as a button would, and run the app logic.
+    pub fn click(&mut self, id: Id) {
+        self.app_data.queue_action(id, Action::Clicked);
+        self.run();
+    }
+
+    /// Change the text of the element, as a text box would, and run the
+    /// app logic.
+    pub fn set_text(&mut self, id: Id, text: impl Into<String>) {
+        self.app_data
+            .queue_action(id, Action::TextChanged(text.into()));
+        self.run();
+    }
+
+    /// Toggle the element, as a checkbox would, and run the app logic.
+    pub fn toggle(&mut self, id: Id, checked: bool) {
+        self.app_data.queue_action(id, Action::Toggled(checked));
+        self.run();
+    }
+
+    /// Change the value of the element, as a slider or stepper would, and
+    /// run the app logic.
+    pub fn set_value(&mut self, id: Id, value: f64) {
+        self.app_data.queue_action(id, Action::ValueChanged(value));
+        self.run();
+    }
+
+    /// Scroll the element to the offset, as a scroll container would, and
+    /// run the app logic.
+    pub fn scroll(&mut self, id: Id, offset: impl Into<Vec2>) {
+        self.app_data
+            .queue_action(id, Action::Scrolled(offset.into()));
+        self.run();
+    }
+
+    /// Resize the viewport of the element, as a scroll container would,
+    /// and run the app logic.
+    pub fn resize(&mut self, id: Id, size: impl Into<Size>) {
+        self.app_data.queue_action(id, Action::Resized(size.into()));
+        self.run();
+    }
+
+    /// Close the window, as the user would, and run the app logic.
+    pub fn close_window(&mut self, id: Id) {
+        self.app_data.queue_action(id, Action::WindowClosed);
+        self.run();
+    }
+
+    /// Deliver an action from a remote renderer, and run the app logic.
+    pub fn remote_action(&mut self, action: WireAction) {
+        self.app_data
+            .queue_action(action.id, action.action.into_action());
+        self.run();
+    }
+
+    /// Deliver a user-defined action to the element, as a custom widget
+    /// would, and run the app logic.
+    pub fn custom_action<T: Any + Send + Sync>(&mut self, id: Id, action: T) {
+        self.app_data.queue_custom(id, action);
+        self.run();
+    }
+
+    /// The current tree.
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// All views of type `V` in the tree, in order, with their ids.
+    pub fn views<V: 'static>(&self) -> impl Iterator<Item = (Id, &V)> {
+        self.tree.iter().filter_map(|(id, body)| match body {
+            Payload::View(view) => view.as_any().downcast_ref().map(|v| (id, v)),
+            _ => None,
+        })
+    }
+
+    /// The id of the first view of type `V` matching the predicate.
+    pub fn find<V: 'static>(&self, pred: impl Fn(&V) -> bool) -> Option<Id> {
+        self.views().find(|(_, v)| pred(v)).map(|(id, _)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::Headless;
+    use crate::{Button, Column, Id, Label, Slider, Stepper, Switch, TextBox};
+
+    #[test]
+    fn click_updates_state() {
+        let mut app = Headless::new(|cx| {
+            Column::new().build(cx, |cx| {
+                cx.use_state(
+                    || 0,
+                    |cx, count| {
+                        Label::new(format!("current count: {}", count)).build(cx);
+                        if Button::new("Increment").build(cx) {
+                            *count += 1;
+                        }
+                    },
+                );
+            });
+        });
+        app.run();
+        assert!(app
+            .find(|l: &Label| l.text() == "current count: 0")
+            .is_some());
+        let button = app.find(|b: &Button| b.text() == "Increment").unwrap();
+        app.click(button);
+        assert!(app
+            .find(|l: &Label| l.text() == "current count: 1")
+            .is_some());
+        app.click(button);
+        assert!(app
+            .find(|l: &Label| l.text() == "current count: 2")
+            .is_some());
+        // The button keeps its identity across runs.
+        assert_eq!(app.find(|b: &Button| b.text() == "Increment"), Some(button));
+    }
+
+    #[test]
+    fn click_reruns_only_for_views_built_before_it() {
+        // With the button first, the label is built after the click is
+        // taken, so one run is enough; with the label first, it is built
+        // again in a second run.
+        for &(button_first, runs_per_click) in &[(true, 1), (false, 2)] {
+            let runs = Rc::new(Cell::new(0));
+            let counted = runs.clone();
+            let mut count = 0;
+            let mut app = Headless::new(move |cx| {
+                counted.set(counted.get() + 1);
+                Column::new().build(cx, |cx| {
+                    if button_first && Button::new("Increment").build(cx) {
+                        count += 1;
+                    }
+                    Label::new(format!("current count: {}", count)).build(cx);
+                    if !button_first && Button::new("Increment").build(cx) {
+                        count += 1;
+                    }
+                });
+            });
+            app.run();
+            assert_eq!(runs.get(), 1);
+            let button = app.find(|b: &Button| b.text() == "Increment").unwrap();
+            app.click(button);
+            assert_eq!(runs.get(), 1 + runs_per_click);
+            assert!(app
+                .find(|l: &Label| l.text() == "current count: 1")
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn set_text_round_trips() {
+        let mut text = String::from("hello");
+        let mut app = Headless::new(move |cx| {
+            if let Some(new_text) = TextBox::new(text.clone()).build(cx) {
+                text = new_text;
+            }
+            Label::new(format!("text: {}", text)).build(cx);
+        });
+        app.run();
+        let text_box = app.find(|t: &TextBox| t.0 == "hello").unwrap();
+        app.set_text(text_box, "world");
+        assert_eq!(app.find(|t: &TextBox| t.0 == "world"), Some(text_box));
+        assert!(app.find(|l: &Label| l.text() == "text: world").is_some());
+    }
+
+    #[test]
+    fn find_returns_the_matching_view() {
+        let mut app = Headless::new(|cx| {
+            Column::new().build(cx, |cx| {
+                Label::new("a").build(cx);
+                Button::new("a").build(cx);
+                Label::new("b").build(cx);
+            });
+        });
+        app.run();
+        let labels: Vec<(Id, &str)> = app.views::<Label>().map(|(id, l)| (id, l.text())).collect();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[1].1, "b");
+        assert_eq!(app.find(|l: &Label| l.text() == "b"), Some(labels[1].0));
+        assert_eq!(app.find(|l: &Label| l.text() == "a"), Some(labels[0].0));
+        assert_ne!(app.find(|b: &Button| b.text() == "a"), Some(labels[0].0));
+        assert_eq!(app.find(|l: &Label| l.text() == "c"), None);
+    }
+
+    #[test]
+    fn custom_actions_are_left_for_the_app_logic() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let logged = log.clone();
+        let mut app = Headless::new(move |cx| {
+            let clicks = Button::new("button").build_count(cx);
+            let id = cx.last_id().unwrap();
+            let custom: Vec<u32> = cx
+                .take_custom_actions::<u32>(id)
+                .iter()
+                .map(|a| **a)
+                .collect();
+            logged.borrow_mut().push((clicks, custom));
+            let _ = TextBox::new("text").build(cx);
+        });
+        app.run();
+        let button = app.find(|b: &Button| b.text() == "button").unwrap();
+        app.custom_action(button, 7u32);
+        assert!(log.borrow().contains(&(0, vec![7])));
+        assert!(log.borrow().iter().all(|(clicks, _)| *clicks == 0));
+        // A built-in view ignores an action it doesn't produce.
+        let text_box = app.find(|t: &TextBox| t.0 == "text").unwrap();
+        app.custom_action(text_box, 7u32);
+        // The click is taken by a button built first, so one run is enough.
+        app.click(button);
+        assert_eq!(log.borrow().last(), Some(&(1, vec![])));
+    }
+
+    #[test]
+    fn slider_snaps_to_its_step() {
+        let value = Rc::new(Cell::new(0.0));
+        let shared = value.clone();
+        let mut app = Headless::new(move |cx| {
+            let slider = Slider::new(shared.get()).range(0.0, 10.0).step(2.5);
+            shared.set(slider.build(cx));
+        });
+        app.run();
+        let slider = app.find(|_: &Slider| true).unwrap();
+        app.set_value(slider, 6.0);
+        assert_eq!(value.get(), 5.0);
+        app.set_value(slider, 6.5);
+        assert_eq!(value.get(), 7.5);
+        app.set_value(slider, 12.0);
+        assert_eq!(value.get(), 10.0);
+        assert_eq!(app.find(|s: &Slider| s.value == 10.0), Some(slider));
+    }
+
+    #[test]
+    fn stepper_clamps_to_its_range() {
+        let values = Rc::new(Cell::new((5.0, 5.0)));
+        let shared = values.clone();
+        let mut app = Headless::new(move |cx| {
+            let (clamped, wrapped) = shared.get();
+            let clamped = Stepper::new(clamped).range(0.0, 10.0).build(cx);
+            let wrapped = Stepper::new(wrapped)
+                .range(0.0, 10.0)
+                .wraparound(true)
+                .build(cx);
+            shared.set((clamped, wrapped));
+        });
+        app.run();
+        let clamped = app.find(|s: &Stepper| !s.wraparound).unwrap();
+        let wrapped = app.find(|s: &Stepper| s.wraparound).unwrap();
+        app.set_value(clamped, 11.0);
+        assert_eq!(values.get(), (10.0, 5.0));
+        app.set_value(clamped, -1.0);
+        assert_eq!(values.get(), (0.0, 5.0));
+        app.set_value(wrapped, 11.0);
+        assert_eq!(values.get(), (0.0, 0.0));
+        app.set_value(wrapped, -1.0);
+        assert_eq!(values.get(), (0.0, 10.0));
+    }
+
+    #[test]
+    fn switch_toggles() {
+        let state = Rc::new(Cell::new(false));
+        let shared = state.clone();
+        let mut app = Headless::new(move |cx| {
+            shared.set(Switch::new(shared.get()).build(cx));
+        });
+        app.run();
+        let switch = app.find(|s: &Switch| !s.state).unwrap();
+        app.toggle(switch, true);
+        assert!(state.get());
+        assert_eq!(app.find(|s: &Switch| s.state), Some(switch));
+        app.toggle(switch, false);
+        assert!(!state.get());
+    }
+
+    #[test]
+    fn disabled_button_reports_no_clicks() {
+        let disabled = Rc::new(Cell::new(false));
+        let clicks = Rc::new(Cell::new(0));
+        let (app_disabled, app_clicks) = (disabled.clone(), clicks.clone());
+        let mut app = Headless::new(move |cx| {
+            let button = Button::new("button").disabled(app_disabled.get());
+            app_clicks.set(app_clicks.get() + button.build_count(cx));
+        });
+        app.run();
+        let button = app.find(|b: &Button| !b.is_disabled()).unwrap();
+        app.click(button);
+        assert_eq!(clicks.get(), 1);
+        disabled.set(true);
+        app.run();
+        assert_eq!(app.find(|b: &Button| b.is_disabled()), Some(button));
+        app.click(button);
+        assert_eq!(clicks.get(), 1);
+    }
+}
@@ -2,6 +2,9 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 
 /// An identifier for an element.
@@ -9,6 +12,7 @@ static ID_COUNTER: AtomicUsize = AtomicUsize::new(0);
 /// It's a bit heavy-handed to have this id as well as widget
 /// id in Druid; likely the two concepts should be unified. But
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Id(usize);
 
 impl Id {
@@ -16,4 +20,15 @@ impl Id {
     pub fn new() -> Id {
         Id(ID_COUNTER.fetch_add(1, Ordering::Relaxed))
     }
+
+    /// An id from a number, for building expected values in tests.
+    #[cfg(test)]
+    pub(crate) fn from_raw(raw: usize) -> Id {
+        Id(raw)
+    }
+
+    /// The id as a number, for display.
+    pub fn to_raw(self) -> usize {
+        self.0
+    }
 }
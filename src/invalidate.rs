@@ -0,0 +1,70 @@
+//! Scheduling runs of the app logic from outside it.
+
+use std::sync::{Arc, Mutex};
+
+use druid::{ExtEventSink, Selector, Target};
+
+use crate::id::Id;
+
+/// A request to run the app logic, from an `Invalidator`.
+pub(crate) const WAKE: Selector<Wake> = Selector::new("crochet.wake");
+
+/// A handle for scheduling a run of the app logic.
+///
+/// State mutated outside the app logic, by a background thread, a channel
+/// or a timer, is picked up on the next run. The handle can be cloned and
+/// sent to other threads. It is obtained from `Cx::invalidator` or
+/// `AppHolder::invalidator`.
+#[derive(Clone)]
+pub struct Invalidator(Arc<dyn Fn(Wake) + Send + Sync>);
+
+/// A request to run the app logic.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Wake {
+    /// Run the app logic.
+    Run,
+    /// Run the app logic, traversing into the element.
+    Node(Id),
+    /// The future of the element resolved.
+    Resolved(Id),
+}
+
+impl Invalidator {
+    pub(crate) fn new(f: impl Fn(Wake) + Send + Sync + 'static) -> Invalidator {
+        Invalidator(Arc::new(f))
+    }
+
+    /// An invalidator submitting wake requests to a Druid app.
+    ///
+    /// Requests are dropped until the sink is set; the app logic runs when
+    /// the window connects anyway.
+    pub(crate) fn for_sink(sink: Arc<Mutex<Option<ExtEventSink>>>) -> Invalidator {
+        Invalidator::new(move |wake| {
+            if let Some(sink) = &*sink.lock().unwrap() {
+                if let Err(e) = sink.submit_command(WAKE, wake, Target::Auto) {
+                    log::error!("failed to submit wake request: {:?}", e);
+                }
+            }
+        })
+    }
+
+    /// Schedule a run of the app logic.
+    ///
+    /// Subtrees skipped by `if_changed` stay skipped, so this is enough
+    /// only if the changed state is read outside of them.
+    pub fn invalidate(&self) {
+        (self.0)(Wake::Run)
+    }
+
+    /// Schedule a run of the app logic that traverses into the element.
+    ///
+    /// Any `if_changed` enclosing the element traverses into it, as if
+    /// the element had a pending action.
+    pub fn invalidate_node(&self, id: Id) {
+        (self.0)(Wake::Node(id))
+    }
+
+    pub(crate) fn resolve(&self, id: Id) {
+        (self.0)(Wake::Resolved(id))
+    }
+}
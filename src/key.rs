@@ -1,7 +1,10 @@
 //! Unique keys and key paths.
 
-use std::hash::Hash;
+use std::any::{Any, TypeId};
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
 use std::panic::Location;
+use std::sync::Arc;
 
 /// A unique call location.
 ///
@@ -11,16 +14,55 @@ use std::panic::Location;
 #[derive(Clone, Copy, Debug)]
 pub struct Caller(&'static Location<'static>);
 
-#[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+/// The identity of an element among its siblings.
+///
+/// Because it may carry a user key, which is reference counted and of
+/// any type, a key is `Clone` but not `Copy`, and has no ordering.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct Key {
     /// The caller that originated the mutation.
     pub(crate) caller: Caller,
     /// The sequence index.
     ///
-    /// At some point, we probably should accommodate user-provided
-    /// stable identities, but for now we just assume that it consists
-    /// of the caller and sequence number.
+    /// This counts previous siblings with the same caller and user key,
+    /// so for user-keyed elements it is nonzero only for duplicates.
     pub(crate) seq_ix: usize,
+    /// A user-provided stable identity, if any.
+    pub(crate) user: Option<UserKey>,
+}
+
+/// A user-provided key.
+///
+/// This wraps any `Hash + Eq + Debug` value, so that it can be stored in
+/// the tree and compared against keys of other types. Values of different
+/// types are never equal. The `Debug` output identifies the key in
+/// snapshots and error messages.
+#[derive(Clone)]
+pub struct UserKey(Arc<dyn AnyKey>);
+
+trait AnyKey: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn eq(&self, other: &dyn AnyKey) -> bool;
+    fn hash(&self, state: &mut dyn Hasher);
+}
+
+impl<T: Hash + Eq + Debug + Send + Sync + 'static> AnyKey for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq(&self, other: &dyn AnyKey) -> bool {
+        if let Some(other) = other.as_any().downcast_ref() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn hash(&self, mut state: &mut dyn Hasher) {
+        Hash::hash(&TypeId::of::<T>(), &mut state);
+        Hash::hash(self, &mut state);
+    }
 }
 
 impl Key {
@@ -28,14 +70,24 @@ impl Key {
         Key {
             caller: caller.into(),
             seq_ix,
+            user: None,
+        }
+    }
+
+    /// A key that also carries a user-provided identity.
+    pub fn with_user_key(caller: impl Into<Caller>, seq_ix: usize, user: UserKey) -> Key {
+        Key {
+            caller: caller.into(),
+            seq_ix,
+            user: Some(user),
         }
     }
 
     /// A null key, which will always equal itself.
     ///
     /// In the future, this might be implemented differently, as Key will
-    /// possibly expand to accommodate user-provided keys and callers from
-    /// different runtimes such as scripting languages.
+    /// possibly expand to accommodate callers from different runtimes
+    /// such as scripting languages.
     pub fn null() -> Key {
         #[track_caller]
         fn null_caller() -> Caller {
@@ -54,6 +106,10 @@ impl Caller {
     fn as_ptr(&self) -> *const Location<'static> {
         self.0
     }
+
+    pub(crate) fn location(&self) -> &'static Location<'static> {
+        self.0
+    }
 }
 
 impl PartialEq for Caller {
@@ -87,3 +143,34 @@ impl From<&'static Location<'static>> for Caller {
         Caller(inner)
     }
 }
+
+impl UserKey {
+    /// Wrap a value to use as a key.
+    ///
+    /// Among the siblings emitted from one call site, each key should be
+    /// unique. A duplicate is told apart from the first by its sequence
+    /// index, so it loses its identity when siblings are reordered.
+    pub fn new(key: impl Hash + Eq + Debug + Send + Sync + 'static) -> UserKey {
+        UserKey(Arc::new(key))
+    }
+}
+
+impl PartialEq for UserKey {
+    fn eq(&self, other: &UserKey) -> bool {
+        self.0.eq(other.0.as_ref())
+    }
+}
+
+impl Eq for UserKey {}
+
+impl Hash for UserKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl Debug for UserKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&*self.0, f)
+    }
+}
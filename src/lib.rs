@@ -15,22 +15,44 @@
 mod any_widget;
 mod app_holder;
 mod cx;
+mod headless;
 mod id;
+mod invalidate;
 mod key;
 mod list;
+mod multi_window;
+mod renderer;
+mod snapshot;
+mod spawner;
 mod state;
 mod tree;
 mod view;
 mod widget;
+mod wire;
 
-pub use any_widget::DruidAppData;
+pub use any_widget::{Action, DruidAppData};
 pub use app_holder::AppHolder;
 pub use cx::Cx;
+pub use headless::Headless;
 pub use id::Id;
+pub use invalidate::Invalidator;
 pub use list::{List, ListData};
+pub use multi_window::MultiWindowApp;
+pub use renderer::{MutationSender, Renderer};
+pub use snapshot::{Node, PayloadKind, Snapshot};
+#[cfg(feature = "async-std")]
+pub use spawner::AsyncStdSpawner;
+pub use spawner::Spawner;
 pub use state::State;
-pub use tree::{MutCursor, MutIterItem, Mutation, MutationIter, Payload, Tree};
+pub use tree::{
+    Invariant, MutCursor, MutIterItem, Mutation, MutationError, MutationIter, Payload, Tree,
+};
 pub use view::{
-    Align, Button, Checkbox, Clicked, Column, Label, Padding, Painter, Row, SizedBox, TextBox,
+    Align, Button, ButtonStyle, Checkbox, Clicked, Column, Label, Opaque, Padding, Painter, Row,
+    Scroll, SizedBox, Slider, Stepper, Switch, TextBox, Window,
 };
 pub use widget::{MutableWidget, SingleChild};
+pub use wire::{
+    ActionData, ButtonStyleData, ViewData, WireAction, WireItem, WireMutation, WirePayload,
+    WireSlot,
+};
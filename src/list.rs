@@ -1,9 +1,21 @@
 //! A list component.
 
+use std::collections::HashMap;
+use std::panic::Location;
+
+use crate::any_widget::Action;
 use crate::id::Id;
-use crate::view::Column;
+use crate::view::{Column, Scroll, SizedBox};
 use crate::Cx;
 
+/// The number of rows a virtualized list builds beyond each edge of the
+/// viewport, so short scrolls don't show blank space.
+const OVERSCAN: usize = 8;
+
+/// The number of rows a virtualized list treats as in view until its
+/// scroll container reports the height of the viewport.
+const DEFAULT_PAGE: usize = 32;
+
 /// A vector that tracks modifications.
 ///
 /// This is a simple wrapper around vec that adds a stable id,
@@ -36,7 +48,14 @@ struct ListItem<T> {
 pub struct List {
     selected: Option<Id>,
     old_selected: Option<Id>,
-    items: Vec<(Id, Id)>,
+    /// The revision of each item in the view tree, by stable id.
+    revs: HashMap<Id, Id>,
+    /// The scroll container of a virtualized list, from the last run.
+    scroll_id: Option<Id>,
+    /// The scroll offset and viewport height of a virtualized list. The
+    /// height is not known until the scroll container is laid out.
+    offset: f64,
+    viewport: Option<f64>,
 }
 
 impl<T> ListItem<T> {
@@ -102,44 +121,116 @@ impl List {
     /// Update the view tree to reflect changes in the list.
     ///
     /// Call the supplied callback for every new or updated item.
+    ///
+    /// Rows are keyed by stable id, so a row that moved, for example by
+    /// `ListData::swap`, is moved in the view tree, keeping its widgets.
+    /// Only rows out of order are moved, so moving one item moves one row.
     pub fn run<T, F>(&mut self, cx: &mut Cx, data: &ListData<T>, mut item_cb: F)
     where
         F: FnMut(&mut Cx, bool, Id, &T),
     {
         // TODO: track caller, pass to the column; or just do a tree around it.
+        let (selected, old_selected) = (self.selected, self.old_selected);
+        let revs = &self.revs;
+        let mut new_revs = HashMap::with_capacity(data.len());
         Column::new().build(cx, |cx| {
-            let mut old_ix = 0;
-            let mut new_items = Vec::new();
-            for item in &data.0 {
-                let id = item.stable_id;
-                let is_selected = self.selected == Some(id);
-                let was_selected = self.old_selected == Some(id);
-                let update_sel = is_selected != was_selected;
-                if let Some(pos) = self.items[old_ix..].iter().position(|(s, _r)| *s == id) {
-                    // Item with this id found; update if needed.
-                    cx.delete(pos);
-                    old_ix += pos;
-                    let has_action = cx.has_action();
-                    if self.items[old_ix].1 != item.rev_id || update_sel || has_action {
-                        cx.begin_update();
+            // The tree finds each row by key, moving only the rows out of
+            // order, and deletes rows not visited.
+            cx.keyed_each(
+                &data.0,
+                |item| item.stable_id,
+                |cx, item| {
+                    let id = item.stable_id;
+                    let is_selected = selected == Some(id);
+                    let was_selected = old_selected == Some(id);
+                    let update_sel = is_selected != was_selected;
+                    let changed = revs.get(&id) != Some(&item.rev_id) || update_sel;
+                    if changed || cx.has_action() {
                         item_cb(cx, is_selected, id, &item.val);
-                        cx.end();
-                        self.items[old_ix].1 = item.rev_id;
                     } else {
-                        cx.skip(1);
+                        cx.skip_rest();
                     }
-                    old_ix += 1;
-                } else {
-                    // No item found with this id; insert.
-                    cx.begin_insert();
-                    item_cb(cx, is_selected, id, &item.val);
-                    cx.end();
+                    new_revs.insert(id, item.rev_id);
+                },
+            );
+        });
+        self.revs = new_revs;
+        self.old_selected = self.selected();
+    }
+
+    /// Update the view tree, building only the rows in view.
+    ///
+    /// The list is placed in a vertical scroll container. Rows within the
+    /// viewport, plus a few on either side, are built, and spacers stand in
+    /// for the rest, so the cost of a run doesn't grow with the length of
+    /// the list. Every row is given a height of `row_height`. Until the
+    /// scroll container reports its size, a default page of rows is built.
+    ///
+    /// The list needs a bounded height, such as that of the window; in a
+    /// `Column` it is as tall as all its rows, and builds them all.
+    ///
+    /// Rows are keyed by stable id, so a row keeps its widgets while it
+    /// stays in view. The callback is called for a row when it comes into
+    /// view, or when its item, its selection or its actions change.
+    pub fn run_virtual<T, F>(
+        &mut self,
+        cx: &mut Cx,
+        data: &ListData<T>,
+        row_height: f64,
+        mut item_cb: F,
+    ) where
+        F: FnMut(&mut Cx, bool, Id, &T),
+    {
+        debug_assert!(row_height > 0.0, "rows must have a positive height");
+        // Take the scroll actions first, so this run builds the rows in view.
+        // The list is their only consumer, so the container is not built with
+        // `Scroll::build`, which would take them too.
+        if let Some(scroll_id) = self.scroll_id {
+            let actions = cx.take_actions_where(scroll_id, |action| {
+                matches!(action, Action::Scrolled(_) | Action::Resized(_))
+            });
+            for action in actions {
+                match action {
+                    Action::Scrolled(offset) => self.offset = offset.y,
+                    Action::Resized(size) => self.viewport = Some(size.height),
+                    _ => (),
                 }
-                new_items.push((item.stable_id, item.rev_id));
             }
-            cx.delete(self.items.len() - old_ix);
-            self.items = new_items;
+        }
+        let len = data.len();
+        let viewport = self.viewport.unwrap_or(DEFAULT_PAGE as f64 * row_height);
+        let first = ((self.offset / row_height) as usize)
+            .saturating_sub(OVERSCAN)
+            .min(len);
+        let last = (((self.offset + viewport) / row_height).ceil() as usize)
+            .saturating_add(OVERSCAN)
+            .min(len);
+        let selected = self.selected;
+        let scroll = Box::new(Scroll::new().vertical());
+        let scroll_id = cx.begin_view(scroll, Location::caller());
+        Column::new().build(cx, |cx| {
+            SizedBox::new()
+                .height(first as f64 * row_height)
+                .build(cx, |_| ());
+            cx.keyed_each(
+                &data.0[first..last],
+                |item| item.stable_id,
+                |cx, item| {
+                    let id = item.stable_id;
+                    let is_selected = selected == Some(id);
+                    cx.if_changed((item.rev_id, is_selected), |cx| {
+                        SizedBox::new()
+                            .height(row_height)
+                            .build(cx, |cx| item_cb(cx, is_selected, id, &item.val));
+                    });
+                },
+            );
+            SizedBox::new()
+                .height((len - last) as f64 * row_height)
+                .build(cx, |_| ());
         });
+        cx.end();
+        self.scroll_id = Some(scroll_id);
         self.old_selected = self.selected();
     }
 
@@ -152,3 +243,143 @@ impl List {
         self.selected
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{List, ListData};
+    use crate::invalidate::Invalidator;
+    use crate::{
+        Cx, DruidAppData, Headless, Id, Label, MutIterItem, Mutation, MutationIter, Payload,
+        Scroll, SizedBox, Tree,
+    };
+
+    /// Run the list against the tree, returning the mutation.
+    fn run(tree: &Tree, list: &mut List, data: &ListData<u32>) -> Mutation {
+        let mut app_data = DruidAppData::default();
+        let invalidator = Invalidator::new(|_| ());
+        let mut cx = Cx::new(tree, &mut app_data, &invalidator, None);
+        list.run(&mut cx, data, |cx, _selected, _id, i| {
+            Label::new(format!("item {}", i)).build(cx);
+        });
+        cx.into_mutation()
+    }
+
+    /// Count the moves, and the inserts and deletes, at every level.
+    fn count_changes(iter: MutationIter, moves: &mut usize, rebuilds: &mut usize) {
+        for item in iter {
+            match item {
+                MutIterItem::Move(_, _, child_iter) => {
+                    *moves += 1;
+                    count_changes(child_iter, moves, rebuilds);
+                }
+                MutIterItem::MoveBack(_) => *moves += 1,
+                MutIterItem::Insert(..) | MutIterItem::Delete(_) => *rebuilds += 1,
+                MutIterItem::Update(_, child_iter) => count_changes(child_iter, moves, rebuilds),
+                MutIterItem::Skip(_) => (),
+            }
+        }
+    }
+
+    /// The id and text of each label in the tree.
+    fn labels(tree: &Tree) -> Vec<(Id, String)> {
+        tree.iter()
+            .filter_map(|(id, body)| match body {
+                Payload::View(view) => view
+                    .as_any()
+                    .downcast_ref::<Label>()
+                    .map(|l| (id, l.text().to_string())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotating_a_list_moves_one_row() {
+        let mut data = ListData::default();
+        for i in 0..10 {
+            data.push(i);
+        }
+        let mut list = List::default();
+        let mut tree = Tree::default();
+        let mutation = run(&tree, &mut list, &data);
+        tree.mutate(mutation);
+        let mut expected = labels(&tree);
+        assert_eq!(expected.len(), 10);
+
+        for ix in 0..9 {
+            data.swap(ix, ix + 1);
+        }
+        let mutation = run(&tree, &mut list, &data);
+        let (mut moves, mut rebuilds) = (0, 0);
+        count_changes(
+            MutationIter::new(&tree, &mutation),
+            &mut moves,
+            &mut rebuilds,
+        );
+        assert_eq!((moves, rebuilds), (1, 0));
+        tree.mutate(mutation);
+        // Every row keeps its label, now in the rotated order.
+        expected.rotate_left(1);
+        assert_eq!(labels(&tree), expected);
+    }
+
+    /// The heights of the spacers before and after the rows built.
+    fn spacers(app: &Headless) -> (f64, f64) {
+        let heights: Vec<f64> = app
+            .views::<SizedBox>()
+            .map(|(_, b)| b.height.unwrap())
+            .collect();
+        (heights[0], heights[heights.len() - 1])
+    }
+
+    #[test]
+    fn virtual_list_builds_the_rows_in_view() {
+        let mut data = ListData::default();
+        for i in 0..1000 {
+            data.push(i);
+        }
+        let selected = data.stable_id_at_ix(45);
+        let list = Rc::new(RefCell::new(List::default()));
+        let app_list = list.clone();
+        let mut app = Headless::new(move |cx| {
+            let mut list = app_list.borrow_mut();
+            list.run_virtual(cx, &data, 10.0, |cx, is_selected, _id, i| {
+                let mark = if is_selected { " *" } else { "" };
+                Label::new(format!("item {}{}", i, mark)).build(cx);
+            });
+        });
+        let rows = |app: &Headless| -> Vec<String> {
+            labels(app.tree()).into_iter().map(|(_, l)| l).collect()
+        };
+        let items = |range: std::ops::Range<u32>| -> Vec<String> {
+            range.map(|i| format!("item {}", i)).collect()
+        };
+
+        // Before the viewport is known, a default page is built.
+        app.run();
+        assert_eq!(rows(&app), items(0..40));
+        assert_eq!(spacers(&app), (0.0, 9600.0));
+
+        let (scroll, _) = app.views::<Scroll>().next().unwrap();
+        app.resize(scroll, (100.0, 100.0));
+        assert_eq!(rows(&app), items(0..18));
+        assert_eq!(spacers(&app), (0.0, 9820.0));
+
+        list.borrow_mut().select(selected);
+        app.scroll(scroll, (0.0, 500.0));
+        let mut expected = items(42..68);
+        expected[3] = "item 45 *".into();
+        assert_eq!(rows(&app), expected);
+        assert_eq!(spacers(&app), (420.0, 9320.0));
+
+        // The selection is kept while the row is out of view.
+        app.scroll(scroll, (0.0, 0.0));
+        assert_eq!(rows(&app), items(0..18));
+        app.scroll(scroll, (0.0, 500.0));
+        assert_eq!(rows(&app), expected);
+        assert_eq!(list.borrow().selected(), Some(selected));
+    }
+}
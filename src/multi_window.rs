@@ -0,0 +1,573 @@
+//! Druid integration for apps with several windows.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use druid::commands::CLOSE_WINDOW;
+use druid::widget::prelude::*;
+use druid::{
+    AppDelegate, AppLauncher, Command, DelegateCtx, ExtEventSink, Handled, Point, Selector, Target,
+    WidgetPod, WindowDesc, WindowId,
+};
+
+use crate::any_widget::{Action, AnyWidget, DruidAppData};
+use crate::invalidate::{Invalidator, WAKE};
+use crate::tree::IterPos;
+use crate::view::{self, View};
+use crate::{Cx, Id, MutIterItem, Mutation, MutationIter, Payload, Spawner, Tree};
+
+/// Apply the pending mutation to the target window.
+const APPLY: Selector = Selector::new("crochet.apply");
+
+/// Run the app logic at the next animation frame of the target window.
+const RUN: Selector = Selector::new("crochet.run");
+
+/// A container for a user application with several windows.
+///
+/// This is the multi-window counterpart of `AppHolder`. The app logic
+/// declares its windows with `Window` views at the top level, and they
+/// are opened, closed and configured to match. The content of each
+/// window is the subtree of its element, so state and identity work as
+/// anywhere else in the tree.
+///
+/// It is installed as the app delegate, by the launcher it creates:
+///
+/// ```ignore
+/// MultiWindowApp::new(move |cx| app_logic.run(cx))
+///     .launcher()
+///     .launch(Default::default())
+/// ```
+///
+/// The app is launched with a single window, which becomes the first
+/// window declared by the app logic.
+pub struct MultiWindowApp {
+    app: Rc<RefCell<App>>,
+}
+
+/// The state shared by the delegate and the root widgets of all windows.
+struct App {
+    tree: Tree,
+    app_logic: Box<dyn FnMut(&mut Cx)>,
+    spawner: Option<Box<dyn Spawner>>,
+    /// The sink for wake requests, once a window is connected.
+    sink: Arc<Mutex<Option<ExtEventSink>>>,
+    invalidator: Invalidator,
+    /// Whether the app logic needs to run at the next animation frame.
+    dirty: bool,
+    /// The windows, one for each top-level element of the tree, in order.
+    windows: Vec<WindowEntry>,
+    /// A mutation not yet applied to all windows.
+    ///
+    /// Widgets can only be mutated from their own window's event context,
+    /// so each other window with changes applies its part when it receives
+    /// the `APPLY` command, and each new window builds its content when it
+    /// connects. Until then, the tree is not mutated, and no run is
+    /// started.
+    pending: Option<(Mutation, Vec<(WindowId, Part)>)>,
+}
+
+/// A window's part of a pending mutation.
+enum Part {
+    /// Build the content of a new window.
+    Build(IterPos),
+    /// Change the content of a window, and its configuration if given.
+    Update(Option<view::Window>, IterPos),
+}
+
+/// A change to the windows, from the top level of a mutation.
+enum WindowChange<'a> {
+    /// Open a window for the element.
+    Open(Id, &'a view::Window, MutationIter<'a>),
+    /// Close the window of the entry removed, unless the user closed it
+    /// already.
+    Close(WindowEntry),
+    /// Change the window of the element.
+    Update(Id, Option<&'a view::Window>, MutationIter<'a>),
+}
+
+struct WindowEntry {
+    id: Id,
+    /// The Druid window, or `None` if closed by the user.
+    window: Option<WindowId>,
+    /// The view the window was last configured from.
+    view: view::Window,
+}
+
+/// The root widget of each window.
+struct WindowRoot {
+    app: Rc<RefCell<App>>,
+    /// The window's content, once built.
+    child: Option<WidgetPod<DruidAppData, AnyWidget>>,
+}
+
+impl MultiWindowApp {
+    pub fn new(app_logic: impl FnMut(&mut Cx) + 'static) -> MultiWindowApp {
+        let sink: Arc<Mutex<Option<ExtEventSink>>> = Default::default();
+        let invalidator = Invalidator::for_sink(sink.clone());
+        let app = App {
+            tree: Tree::default(),
+            app_logic: Box::new(app_logic),
+            spawner: crate::app_holder::default_spawner(),
+            sink,
+            invalidator,
+            dirty: true,
+            windows: Vec::new(),
+            pending: None,
+        };
+        MultiWindowApp {
+            app: Rc::new(RefCell::new(app)),
+        }
+    }
+
+    /// Set the executor for futures spawned by the app logic.
+    ///
+    /// The default is the same as for `AppHolder::with_spawner`.
+    pub fn with_spawner(self, spawner: impl Spawner + 'static) -> MultiWindowApp {
+        self.app.borrow_mut().spawner = Some(Box::new(spawner));
+        self
+    }
+
+    /// A handle for scheduling runs of the app logic from outside it.
+    pub fn invalidator(&self) -> Invalidator {
+        self.app.borrow().invalidator.clone()
+    }
+
+    /// A launcher for the app, with this as its delegate.
+    pub fn launcher(self) -> AppLauncher<DruidAppData> {
+        let root = WindowRoot {
+            app: self.app.clone(),
+            child: None,
+        };
+        AppLauncher::with_window(WindowDesc::new(move || root)).delegate(self)
+    }
+}
+
+impl App {
+    /// Mutate the tree, once all windows have applied the pending mutation.
+    fn settle(&mut self) {
+        if let Some((_, waiting)) = &self.pending {
+            if waiting.is_empty() {
+                let (mutation, _) = self.pending.take().unwrap();
+                self.tree.mutate(mutation);
+            }
+        }
+    }
+
+    /// Schedule a run of the app logic in any open window.
+    fn request_run(&mut self, ctx: &mut DelegateCtx) {
+        self.dirty = true;
+        if let Some(window) = self.windows.iter().find_map(|entry| entry.window) {
+            ctx.submit_command(RUN.to(Target::Window(window)));
+        }
+    }
+}
+
+impl AppDelegate<DruidAppData> for MultiWindowApp {
+    fn command(
+        &mut self,
+        ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut DruidAppData,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(wake) = cmd.get(WAKE) {
+            // Handled here, so it is applied once rather than per window.
+            data.wake(*wake);
+            self.app.borrow_mut().request_run(ctx);
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+
+    fn window_removed(
+        &mut self,
+        id: WindowId,
+        data: &mut DruidAppData,
+        _env: &Env,
+        ctx: &mut DelegateCtx,
+    ) {
+        let mut app = self.app.borrow_mut();
+        if let Some((_, waiting)) = &mut app.pending {
+            waiting.retain(|(window, _)| *window != id);
+        }
+        app.settle();
+        // Windows closed by the app logic are already gone.
+        if let Some(entry) = app.windows.iter_mut().find(|e| e.window == Some(id)) {
+            entry.window = None;
+            data.queue_action(entry.id, Action::WindowClosed);
+            app.request_run(ctx);
+        }
+    }
+}
+
+impl WindowRoot {
+    /// Run the app logic, mutating the tree and the windows.
+    ///
+    /// Changes to this window are applied directly. Other windows are sent
+    /// the `APPLY` command, and new windows build their content when they
+    /// connect.
+    fn run_app_logic(&mut self, ctx: &mut EventCtx, data: &mut DruidAppData) {
+        let mut app = self.app.borrow_mut();
+        let app = &mut *app;
+        app.sink
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| ctx.get_external_handle());
+        let mut cx = Cx::new(&app.tree, data, &app.invalidator, app.spawner.as_deref());
+        (app.app_logic)(&mut cx);
+        let mutation = cx.into_mutation();
+        let this = ctx.window_id();
+        let mut waiting = Vec::new();
+        let mut_iter = MutationIter::new(&app.tree, &mutation);
+        for change in reconcile(&mut app.windows, mut_iter) {
+            match change {
+                WindowChange::Open(id, view, mut_iter) => {
+                    let entry = app.windows.iter_mut().find(|e| e.id == id).unwrap();
+                    if self.child.is_none() {
+                        // The window the app was launched with.
+                        ctx.window().set_title(&view.title);
+                        if let Some(size) = view.size {
+                            ctx.window().set_size(size);
+                        }
+                        let mut widget = view.make_widget(id);
+                        widget.mutate_update(ctx, None, mut_iter);
+                        self.child = Some(WidgetPod::new(widget));
+                        ctx.children_changed();
+                        entry.window = Some(this);
+                    } else {
+                        let root = WindowRoot {
+                            app: self.app.clone(),
+                            child: None,
+                        };
+                        let mut desc = WindowDesc::new(move || root).title(view.title.clone());
+                        if let Some(size) = view.size {
+                            desc = desc.window_size(size);
+                        }
+                        let window = desc.id;
+                        ctx.new_window(desc);
+                        waiting.push((window, Part::Build(mut_iter.pos())));
+                        entry.window = Some(window);
+                    }
+                }
+                WindowChange::Close(entry) => {
+                    if let Some(window) = entry.window {
+                        ctx.submit_command(CLOSE_WINDOW.to(Target::Window(window)));
+                    }
+                }
+                WindowChange::Update(id, view, mut_iter) => {
+                    let entry = app.windows.iter_mut().find(|e| e.id == id).unwrap();
+                    match entry.window {
+                        Some(window) if window == this => {
+                            apply(ctx, &mut self.child, &mut entry.view, view, mut_iter);
+                        }
+                        Some(window) => {
+                            let part = Part::Update(view.cloned(), mut_iter.pos());
+                            waiting.push((window, part));
+                            ctx.submit_command(APPLY.to(Target::Window(window)));
+                        }
+                        // Closed by the user, so there is nothing to update.
+                        None => (),
+                    }
+                }
+            }
+        }
+        app.pending = Some((mutation, waiting));
+        app.settle();
+        // As in `AppHolder`, run once more if some elements may be stale.
+        app.dirty = data.has_pending();
+        if app.dirty {
+            ctx.request_anim_frame();
+        }
+        data.end_run();
+    }
+
+    /// Apply this window's part of the pending mutation.
+    fn apply_pending(&mut self, ctx: &mut EventCtx) {
+        let mut app = self.app.borrow_mut();
+        let app = &mut *app;
+        let this = ctx.window_id();
+        if let Some((mutation, waiting)) = &app.pending {
+            if let Some(pos) = waiting.iter().position(|(window, _)| *window == this) {
+                let entry = app
+                    .windows
+                    .iter_mut()
+                    .find(|entry| entry.window == Some(this))
+                    .unwrap();
+                match &waiting[pos].1 {
+                    Part::Build(iter_pos) => {
+                        let mut_iter = MutationIter::resume(&app.tree, mutation, *iter_pos);
+                        let mut widget = entry.view.make_widget(entry.id);
+                        widget.mutate_update(ctx, None, mut_iter);
+                        self.child = Some(WidgetPod::new(widget));
+                        ctx.children_changed();
+                    }
+                    Part::Update(view, iter_pos) => {
+                        let mut_iter = MutationIter::resume(&app.tree, mutation, *iter_pos);
+                        apply(
+                            ctx,
+                            &mut self.child,
+                            &mut entry.view,
+                            view.as_ref(),
+                            mut_iter,
+                        );
+                    }
+                }
+            }
+        }
+        if let Some((_, waiting)) = &mut app.pending {
+            waiting.retain(|(window, _)| *window != this);
+        }
+        app.settle();
+    }
+}
+
+/// Reorder the window entries to match the top level of a mutation,
+/// returning the changes to make to the windows.
+///
+/// Entries are added for new windows, with no Druid window yet, and
+/// removed for closed ones.
+fn reconcile<'a>(
+    windows: &mut Vec<WindowEntry>,
+    mut_iter: MutationIter<'a>,
+) -> Vec<WindowChange<'a>> {
+    let mut changes = Vec::new();
+    let mut ix = 0;
+    for item in mut_iter {
+        if let MutIterItem::Move(n, ..) = &item {
+            let entry = windows.remove(ix + n);
+            windows.insert(ix, entry);
+        }
+        match item {
+            MutIterItem::Skip(n) => ix += n,
+            MutIterItem::MoveBack(n) => {
+                let entry = windows.remove(ix - 1 - n);
+                ix -= 1;
+                windows.insert(ix, entry);
+            }
+            MutIterItem::Delete(n) => {
+                for entry in windows.drain(ix..ix + n) {
+                    changes.push(WindowChange::Close(entry));
+                }
+            }
+            MutIterItem::Insert(id, body, mut_iter) => {
+                let view = as_window(body);
+                let entry = WindowEntry {
+                    id,
+                    window: None,
+                    view: view.clone(),
+                };
+                windows.insert(ix, entry);
+                changes.push(WindowChange::Open(id, view, mut_iter));
+                ix += 1;
+            }
+            MutIterItem::Update(body, mut_iter) | MutIterItem::Move(_, body, mut_iter) => {
+                let view = body.map(as_window);
+                changes.push(WindowChange::Update(windows[ix].id, view, mut_iter));
+                ix += 1;
+            }
+        }
+    }
+    changes
+}
+
+/// Update a window's configuration and content.
+fn apply(
+    ctx: &mut EventCtx,
+    child: &mut Option<WidgetPod<DruidAppData, AnyWidget>>,
+    view: &mut view::Window,
+    new: Option<&view::Window>,
+    mut_iter: MutationIter,
+) {
+    if let Some(new) = new {
+        if new.title != view.title {
+            ctx.window().set_title(&new.title);
+        }
+        if new.size != view.size {
+            if let Some(size) = new.size {
+                ctx.window().set_size(size);
+            }
+        }
+        *view = new.clone();
+    }
+    // The configuration is applied here; the content's widget doesn't
+    // read the window's payload.
+    if let Some(child) = child {
+        child.with_event_context(ctx, |child, ctx| {
+            child.mutate_update(ctx, None, mut_iter);
+        });
+    }
+}
+
+fn as_window(body: &Payload) -> &view::Window {
+    match body {
+        Payload::View(v) => v.as_any().downcast_ref(),
+        _ => None,
+    }
+    .expect("the top level of a multi-window app must be windows")
+}
+
+impl Widget<DruidAppData> for WindowRoot {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        if let Event::WindowConnected = event {
+            // A new window builds its content in its own context.
+            self.apply_pending(ctx);
+        }
+        if let Event::Command(cmd) = event {
+            if cmd.is(APPLY) {
+                self.apply_pending(ctx);
+                ctx.set_handled();
+                return;
+            }
+            if cmd.is(RUN) {
+                ctx.request_anim_frame();
+            }
+        }
+        let before = data.clone();
+        if let Some(child) = &mut self.child {
+            child.event(ctx, event, data, env);
+        }
+        let mut app = self.app.borrow_mut();
+        if !before.same(data) {
+            // A widget queued an action.
+            app.dirty = true;
+        }
+        if app.dirty {
+            match event {
+                // Wait for the previous mutation to reach all windows.
+                Event::WindowConnected | Event::AnimFrame(_) if app.pending.is_none() => {
+                    drop(app);
+                    self.run_app_logic(ctx, data);
+                }
+                _ => ctx.request_anim_frame(),
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        if let Some(child) = &mut self.child {
+            child.lifecycle(ctx, event, data, env);
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        if let Some(child) = &mut self.child {
+            child.update(ctx, data, env);
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        match &mut self.child {
+            Some(child) => {
+                let size = child.layout(ctx, bc, data, env);
+                child.set_layout_rect(ctx, data, env, (Point::ZERO, size).into());
+                size
+            }
+            None => bc.max(),
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        if let Some(child) = &mut self.child {
+            child.paint(ctx, data, env);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconcile, WindowChange, WindowEntry};
+    use crate::invalidate::Invalidator;
+    use crate::{Cx, DruidAppData, Id, Label, MutationIter, Tree, Window};
+
+    /// Run app logic declaring a window for each title, keyed by title,
+    /// and reconcile the entries with the mutation, returning the changes
+    /// with the title of the window changed.
+    fn run(tree: &mut Tree, windows: &mut Vec<WindowEntry>, titles: &[&str]) -> Vec<String> {
+        let mut app_data = DruidAppData::default();
+        let invalidator = Invalidator::new(|_| ());
+        let mut cx = Cx::new(tree, &mut app_data, &invalidator, None);
+        cx.keyed_each(
+            titles.iter().copied(),
+            |title| title.to_string(),
+            |cx, title| {
+                Window::new(title).build(cx, |cx| {
+                    Label::new(title).build(cx);
+                });
+            },
+        );
+        let mutation = cx.into_mutation();
+        let changes = reconcile(windows, MutationIter::new(tree, &mutation));
+        let changes = changes
+            .into_iter()
+            .map(|change| match change {
+                WindowChange::Open(_, view, _) => format!("open {}", view.title),
+                WindowChange::Close(entry) => format!("close {}", entry.view.title),
+                WindowChange::Update(id, _, _) => {
+                    let entry = windows.iter().find(|entry| entry.id == id).unwrap();
+                    format!("update {}", entry.view.title)
+                }
+            })
+            .collect();
+        tree.mutate(mutation);
+        changes
+    }
+
+    fn titles(windows: &[WindowEntry]) -> Vec<&str> {
+        windows
+            .iter()
+            .map(|entry| entry.view.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn windows_open_close_and_reorder() {
+        let mut tree = Tree::default();
+        let mut windows = Vec::new();
+
+        let changes = run(&mut tree, &mut windows, &["a", "b", "c"]);
+        assert_eq!(changes, vec!["open a", "open b", "open c"]);
+        assert_eq!(titles(&windows), vec!["a", "b", "c"]);
+        let ids: Vec<Id> = windows.iter().map(|entry| entry.id).collect();
+
+        // Moving a window keeps its entry, and so its Druid window.
+        let changes = run(&mut tree, &mut windows, &["c", "a", "b"]);
+        assert_eq!(changes, vec!["update c"]);
+        assert_eq!(titles(&windows), vec!["c", "a", "b"]);
+        assert_eq!(
+            windows.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            vec![ids[2], ids[0], ids[1]]
+        );
+
+        let changes = run(&mut tree, &mut windows, &["c", "b"]);
+        assert_eq!(changes, vec!["close a"]);
+        assert_eq!(titles(&windows), vec!["c", "b"]);
+
+        let changes = run(&mut tree, &mut windows, &["d", "c", "b"]);
+        assert_eq!(changes, vec!["open d"]);
+        assert_eq!(titles(&windows), vec!["d", "c", "b"]);
+
+        // Nothing changes when the windows stay the same.
+        assert!(run(&mut tree, &mut windows, &["d", "c", "b"]).is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! A Druid widget that renders a tree driven from elsewhere.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use druid::widget::prelude::*;
+use druid::{ExtEventSink, Point, Selector, Target, WidgetPod};
+
+use crate::any_widget::{AnyWidget, DruidAppData};
+use crate::wire::{ActionData, WireAction, WireMutation};
+use crate::{widget, Mutation, MutationIter, Tree};
+
+/// Notification that mutations are waiting to be applied.
+const MUTATIONS: Selector = Selector::new("crochet.mutations");
+
+/// A container rendering app logic that runs elsewhere.
+///
+/// This is the other end of the wire format: instead of running app logic,
+/// it applies `WireMutation`s, which can come from another thread or
+/// process, to its own copy of the tree. Actions produced by its widgets
+/// are passed to a callback, to be sent back to the app logic.
+///
+/// ```ignore
+/// let renderer = Renderer::new(move |action| send_to_app(action));
+/// let sender = renderer.mutation_sender();
+/// // On receiving a mutation from the app logic:
+/// sender.send(mutation);
+/// ```
+pub struct Renderer {
+    tree: Tree,
+    child: WidgetPod<DruidAppData, AnyWidget>,
+    queue: Arc<Mutex<VecDeque<WireMutation>>>,
+    /// The sink for notifications, once the window is connected.
+    sink: Arc<Mutex<Option<ExtEventSink>>>,
+    on_action: Box<dyn FnMut(WireAction)>,
+}
+
+/// A handle for sending mutations to a `Renderer`, from any thread.
+#[derive(Clone)]
+pub struct MutationSender {
+    queue: Arc<Mutex<VecDeque<WireMutation>>>,
+    sink: Arc<Mutex<Option<ExtEventSink>>>,
+}
+
+impl Renderer {
+    pub fn new(on_action: impl FnMut(WireAction) + 'static) -> Renderer {
+        let root = AnyWidget::MutableWidget(Box::new(widget::SizedBox::new(&Default::default())));
+        Renderer {
+            tree: Tree::default(),
+            child: WidgetPod::new(root),
+            queue: Default::default(),
+            sink: Default::default(),
+            on_action: Box::new(on_action),
+        }
+    }
+
+    pub fn mutation_sender(&self) -> MutationSender {
+        MutationSender {
+            queue: self.queue.clone(),
+            sink: self.sink.clone(),
+        }
+    }
+
+    /// Apply the waiting mutations, in order.
+    fn apply_mutations(&mut self, ctx: &mut EventCtx) {
+        while let Some(mutation) = self.next_mutation() {
+            let mut_iter = MutationIter::new(&self.tree, &mutation);
+            self.child.with_event_context(ctx, |child, ctx| {
+                child.mutate_update(ctx, None, mut_iter);
+            });
+            self.tree.mutate(mutation);
+        }
+    }
+
+    /// Take the next waiting mutation.
+    ///
+    /// It comes from elsewhere, so with debug assertions it is checked
+    /// against the tree before any widget sees it.
+    fn next_mutation(&mut self) -> Option<Mutation> {
+        let wire = self.queue.lock().unwrap().pop_front()?;
+        let mutation = Mutation::from_wire(wire);
+        mutation.assert_valid(&self.tree);
+        Some(mutation)
+    }
+}
+
+impl MutationSender {
+    /// Send a mutation, to be applied after those sent before it.
+    ///
+    /// Mutations sent before the window is connected are applied when it
+    /// connects.
+    pub fn send(&self, mutation: WireMutation) {
+        self.queue.lock().unwrap().push_back(mutation);
+        if let Some(sink) = &*self.sink.lock().unwrap() {
+            if let Err(e) = sink.submit_command(MUTATIONS, (), Target::Auto) {
+                log::error!("failed to submit mutations: {:?}", e);
+            }
+        }
+    }
+}
+
+impl Widget<DruidAppData> for Renderer {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        match event {
+            Event::WindowConnected => {
+                // Set the sink first, so no mutation is left waiting.
+                *self.sink.lock().unwrap() = Some(ctx.get_external_handle());
+                self.apply_mutations(ctx);
+            }
+            Event::Command(cmd) if cmd.is(MUTATIONS) => {
+                self.apply_mutations(ctx);
+                ctx.set_handled();
+                return;
+            }
+            _ => (),
+        }
+        self.child.event(ctx, event, data, env);
+        for (id, action) in data.take_all_actions() {
+            if let Some(action) = ActionData::from_action(&action) {
+                (self.on_action)(WireAction { id, action });
+            }
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.child.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.child.update(ctx, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        let size = self.child.layout(ctx, bc, data, env);
+        self.child
+            .set_layout_rect(ctx, data, env, (Point::ZERO, size).into());
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        self.child.paint(ctx, data, env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::wire::{WireItem, WireMutation, WirePayload, WireSlot};
+    use crate::Id;
+
+    #[test]
+    #[cfg_attr(not(debug_assertions), ignore)]
+    #[should_panic(expected = "invalid mutation: mutation covers more slots than the tree has")]
+    fn invalid_mutations_are_reported() {
+        let mut renderer = Renderer::new(|_| ());
+        let sender = renderer.mutation_sender();
+        let begin = WireSlot::Begin {
+            id: Id::new(),
+            body: WirePayload::Transparent,
+        };
+        sender.send(WireMutation {
+            items: vec![WireItem::Insert(vec![begin, WireSlot::End])],
+        });
+        let mutation = renderer.next_mutation().unwrap();
+        renderer.tree.mutate(mutation);
+        // The tree has two slots, so skipping three is out of bounds.
+        sender.send(WireMutation {
+            items: vec![WireItem::Skip(3)],
+        });
+        renderer.next_mutation();
+    }
+}
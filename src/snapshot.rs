@@ -0,0 +1,344 @@
+//! Structured snapshots of the tree, for tests and debugging.
+
+use std::fmt::Write;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::id::Id;
+use crate::key::Key;
+use crate::tree::Payload;
+
+/// A snapshot of the tree, obtained from `Tree::snapshot`.
+///
+/// Snapshots can be compared, ignoring ids, so tests can check the shape
+/// of the tree after a run. For looking at them, they can be exported as
+/// a Graphviz graph or, with the `serde` feature, as JSON.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Snapshot {
+    /// The top-level elements.
+    pub nodes: Vec<Node>,
+}
+
+/// A snapshot of one element of the tree.
+///
+/// Ids are allocated from a global counter, so they differ between runs.
+/// Equality of nodes ignores them and compares only the structure.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Node {
+    /// The source file of the call site that emitted the element.
+    pub file: &'static str,
+    pub line: u32,
+    pub column: u32,
+    /// The number of previous siblings with the same call site and user
+    /// key.
+    pub seq_ix: usize,
+    /// The `Debug` output of the user-provided key, if any.
+    pub user_key: Option<String>,
+    pub id: Id,
+    pub kind: PayloadKind,
+    /// The `Debug` output of the view, if the element is a view.
+    pub view: Option<String>,
+    pub children: Vec<Node>,
+}
+
+/// The kind of payload of an element.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum PayloadKind {
+    Placeholder,
+    Fragment,
+    Future,
+    State,
+    View,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.file == other.file
+            && self.line == other.line
+            && self.column == other.column
+            && self.seq_ix == other.seq_ix
+            && self.user_key == other.user_key
+            && self.kind == other.kind
+            && self.view == other.view
+            && self.children == other.children
+    }
+}
+
+impl Node {
+    pub(crate) fn new(key: &Key, id: Id, body: &Payload, children: Vec<Node>) -> Node {
+        let location = key.caller.location();
+        let (kind, view) = match body {
+            Payload::Placeholder => (PayloadKind::Placeholder, None),
+            Payload::Fragment => (PayloadKind::Fragment, None),
+            Payload::Future(_) => (PayloadKind::Future, None),
+            Payload::State(_) => (PayloadKind::State, None),
+            Payload::View(view) => (PayloadKind::View, Some(format!("{:?}", view))),
+        };
+        Node {
+            file: location.file(),
+            line: location.line(),
+            column: location.column(),
+            seq_ix: key.seq_ix,
+            user_key: key.user.as_ref().map(|user| format!("{:?}", user)),
+            id,
+            kind,
+            view,
+            children,
+        }
+    }
+
+    /// A one-line description, for labels.
+    fn label(&self) -> String {
+        let mut label = format!("{:?}", self.kind);
+        if let Some(view) = &self.view {
+            write!(label, " {}", view).unwrap();
+        }
+        label
+    }
+}
+
+impl Snapshot {
+    /// Export the snapshot as JSON.
+    ///
+    /// The output is an array of nodes, each an object with the fields of
+    /// `Node`, pretty-printed so that diffs of two snapshots line up.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        let mut out = serde_json::to_string_pretty(&self.nodes).unwrap();
+        out.push('\n');
+        out
+    }
+
+    /// Export the snapshot as a Graphviz graph, in the DOT language.
+    ///
+    /// Each element is a node, labeled with its payload and call site, with
+    /// an edge from its parent. Render it with, for example,
+    /// `dot -Tsvg tree.dot > tree.svg`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph tree {\n    node [shape=box];\n");
+        write_dot_nodes(&mut out, None, &self.nodes);
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn write_dot_nodes(out: &mut String, parent: Option<Id>, nodes: &[Node]) {
+    for node in nodes {
+        let id = node.id.to_raw();
+        let label = format!(
+            "{}\n{}:{}:{} #{}",
+            node.label(),
+            node.file,
+            node.line,
+            node.column,
+            node.seq_ix
+        );
+        writeln!(out, "    n{} [label={}];", id, dot_str(&label)).unwrap();
+        if let Some(parent) = parent {
+            writeln!(out, "    n{} -> n{};", parent.to_raw(), id).unwrap();
+        }
+        write_dot_nodes(out, Some(node.id), &node.children);
+    }
+}
+
+fn dot_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use crate::{Column, Headless, Label};
+
+    fn node(id: usize, kind: PayloadKind, view: Option<&str>, children: Vec<Node>) -> Node {
+        Node {
+            file: "src/main.rs",
+            line: 3,
+            column: 5,
+            seq_ix: 0,
+            user_key: None,
+            id: Id::from_raw(id),
+            kind,
+            view: view.map(String::from),
+            children,
+        }
+    }
+
+    fn sample() -> Snapshot {
+        let label = node(
+            2,
+            PayloadKind::View,
+            Some("Label(\"a\\b\"\n\t\u{1}\")"),
+            vec![],
+        );
+        Snapshot {
+            nodes: vec![node(1, PayloadKind::Fragment, None, vec![label])],
+        }
+    }
+
+    #[test]
+    fn equality_ignores_ids() {
+        let mut other = sample();
+        other.nodes[0].id = Id::from_raw(10);
+        other.nodes[0].children[0].id = Id::from_raw(11);
+        assert_eq!(sample(), other);
+        other.nodes[0].children[0].seq_ix = 1;
+        assert_ne!(sample(), other);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_golden() {
+        let expected = r#"[
+  {
+    "file": "src/main.rs",
+    "line": 3,
+    "column": 5,
+    "seq_ix": 0,
+    "user_key": null,
+    "id": 1,
+    "kind": "Fragment",
+    "view": null,
+    "children": [
+      {
+        "file": "src/main.rs",
+        "line": 3,
+        "column": 5,
+        "seq_ix": 0,
+        "user_key": null,
+        "id": 2,
+        "kind": "View",
+        "view": "Label(\"a\\b\"\n\t\u0001\")",
+        "children": []
+      }
+    ]
+  }
+]
+"#;
+        assert_eq!(sample().to_json(), expected);
+    }
+
+    /// Number the nodes in preorder and make lines relative to `line`,
+    /// as real ids depend on what else ran.
+    #[cfg(feature = "serde")]
+    fn normalize(nodes: &mut [Node], line: u32, next: &mut usize) {
+        for node in nodes {
+            *next += 1;
+            node.id = Id::from_raw(*next);
+            node.line -= line;
+            normalize(&mut node.children, line, next);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_golden_from_headless() {
+        let line = line!();
+        let mut app = Headless::new(|cx| {
+            Column::new().build(cx, |cx| {
+                cx.keyed_each(
+                    vec!["a", "b"],
+                    |s| *s,
+                    |cx, s| {
+                        Label::new(s).build(cx);
+                    },
+                );
+            });
+        });
+        app.run();
+        let mut snapshot = app.tree().snapshot();
+        normalize(&mut snapshot.nodes, line, &mut 0);
+        // User keys are recorded by their `Debug` output.
+        let expected = r#"[
+  {
+    "file": "src/snapshot.rs",
+    "line": 2,
+    "column": 27,
+    "seq_ix": 0,
+    "user_key": null,
+    "id": 1,
+    "kind": "View",
+    "view": "Column",
+    "children": [
+      {
+        "file": "src/snapshot.rs",
+        "line": 3,
+        "column": 20,
+        "seq_ix": 0,
+        "user_key": "\"a\"",
+        "id": 2,
+        "kind": "Placeholder",
+        "view": null,
+        "children": [
+          {
+            "file": "src/snapshot.rs",
+            "line": 7,
+            "column": 39,
+            "seq_ix": 0,
+            "user_key": null,
+            "id": 3,
+            "kind": "View",
+            "view": "Label(\"a\")",
+            "children": []
+          }
+        ]
+      },
+      {
+        "file": "src/snapshot.rs",
+        "line": 3,
+        "column": 20,
+        "seq_ix": 0,
+        "user_key": "\"b\"",
+        "id": 4,
+        "kind": "Placeholder",
+        "view": null,
+        "children": [
+          {
+            "file": "src/snapshot.rs",
+            "line": 7,
+            "column": 39,
+            "seq_ix": 0,
+            "user_key": null,
+            "id": 5,
+            "kind": "View",
+            "view": "Label(\"b\")",
+            "children": []
+          }
+        ]
+      }
+    ]
+  }
+]
+"#;
+        assert_eq!(snapshot.to_json(), expected);
+    }
+
+    #[test]
+    fn dot_golden() {
+        // Only quotes, backslashes and newlines need escaping in DOT.
+        let expected = concat!(
+            "digraph tree {\n",
+            "    node [shape=box];\n",
+            "    n1 [label=\"Fragment\\nsrc/main.rs:3:5 #0\"];\n",
+            "    n2 [label=\"View Label(\\\"a\\\\b\\\"\\n\t\u{1}\\\")\\nsrc/main.rs:3:5 #0\"];\n",
+            "    n1 -> n2;\n",
+            "}\n",
+        );
+        assert_eq!(sample().to_dot(), expected);
+    }
+}
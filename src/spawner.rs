@@ -0,0 +1,46 @@
+//! Running futures spawned by the app logic.
+
+use futures::future::BoxFuture;
+
+/// An executor for futures spawned by the app logic.
+///
+/// The app logic itself is executor-agnostic; this trait is the one place
+/// where an executor is plugged in, using [`AppHolder::with_spawner`].
+/// Any closure taking a boxed future works as a spawner, so adapting an
+/// executor is usually a one-liner, for example with tokio:
+///
+/// ```ignore
+/// AppHolder::new(app_logic).with_spawner(|future| {
+///     tokio::spawn(future);
+/// })
+/// ```
+///
+/// A single-threaded executor such as `futures::executor::LocalPool` can
+/// be used the same way, which gives deterministic tests control over
+/// when futures make progress.
+///
+/// [`AppHolder::with_spawner`]: crate::AppHolder::with_spawner
+pub trait Spawner {
+    /// Run the future to completion in the background.
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+}
+
+impl<F: Fn(BoxFuture<'static, ()>)> Spawner for F {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        self(future)
+    }
+}
+
+/// A spawner that runs futures on the async-std global executor.
+///
+/// This is the default spawner when the `async-std` feature is enabled.
+#[cfg(feature = "async-std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncStdSpawner;
+
+#[cfg(feature = "async-std")]
+impl Spawner for AsyncStdSpawner {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        async_std::task::spawn(future);
+    }
+}
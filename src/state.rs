@@ -2,6 +2,9 @@
 
 use std::any::Any;
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::future::AbortHandle;
 
 /// An object suitable for storing as state.
 ///
@@ -32,3 +35,70 @@ impl Debug for dyn State {
         write!(f, "state node of type {:?}", Any::type_id(self.as_any()))
     }
 }
+
+/// Local state owned by an element of the tree.
+///
+/// The tree holds one handle and the app logic gets another, so the state
+/// can be mutated in place without producing a tree mutation. Equality is
+/// identity, so an existing state element is always skipped.
+pub(crate) struct StateCell<T>(Arc<Mutex<T>>);
+
+impl<T> StateCell<T> {
+    pub(crate) fn new(val: T) -> StateCell<T> {
+        StateCell(Arc::new(Mutex::new(val)))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<T> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl<T> Clone for StateCell<T> {
+    fn clone(&self) -> Self {
+        StateCell(self.0.clone())
+    }
+}
+
+impl<T> PartialEq for StateCell<T> {
+    fn eq(&self, other: &StateCell<T>) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A memoized computation, stored in the tree.
+///
+/// Two memos are equal if their dependencies are, regardless of result.
+pub(crate) struct Memo {
+    pub(crate) deps: Box<dyn State>,
+    pub(crate) result: Arc<dyn Any + Send + Sync>,
+}
+
+impl PartialEq for Memo {
+    fn eq(&self, other: &Memo) -> bool {
+        self.deps.eq(other.deps.as_ref())
+    }
+}
+
+/// A future spawned by `use_future` or `use_stream`, stored in the tree.
+///
+/// The task delivers its result (or the latest item of a stream) into a
+/// slot it shares with this cell. Dropping the cell, when the element is
+/// deleted or its data changes, aborts the task through the `AbortHandle`
+/// if it is still running, so nothing is delivered to a stale slot.
+pub(crate) struct FutureCell {
+    pub(crate) data: Box<dyn State>,
+    pub(crate) result: Arc<Mutex<Option<Box<dyn State>>>>,
+    pub(crate) abort: AbortHandle,
+}
+
+impl PartialEq for FutureCell {
+    fn eq(&self, other: &FutureCell) -> bool {
+        self.data.eq(other.data.as_ref())
+    }
+}
+
+impl Drop for FutureCell {
+    fn drop(&mut self) {
+        self.abort.abort();
+    }
+}
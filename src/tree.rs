@@ -1,17 +1,26 @@
 //! A tree of render objects.
 
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::panic::Location;
 
 use crate::id::Id;
-use crate::key::{Caller, Key};
+use crate::key::{Caller, Key, UserKey};
+use crate::snapshot::{Node, Snapshot};
 use crate::state::State;
 use crate::view::View;
+use crate::wire::{ViewData, WireItem, WireMutation, WirePayload, WireSlot};
+
+mod validate;
+
+pub use validate::{Invariant, MutationError};
 
 /// The payload of an item in the tree.
 #[derive(Debug)]
 pub enum Payload {
     Placeholder,
-    Future(Id, Box<dyn State>),
+    /// A group of elements, contributing its children to the parent.
+    Fragment,
+    Future(Box<dyn State>),
     State(Box<dyn State>),
     View(Box<dyn View>),
 }
@@ -36,16 +45,32 @@ pub struct Item {
 /// Attributes are TODO.
 ///
 /// Each "begin" item carries a payload. It is also associated with
-/// a key. Currently keys are derived from caller and sequence number,
-/// but we will support user-provided keys also.
+/// a key. Keys are derived from caller and sequence number, optionally
+/// combined with a user-provided key.
 ///
 /// In implementation, this is a somewhat unusual architecture. It is a
-/// list of slots, where each can either push or pop a tree level. But
-/// ideally implementation details are hidden, and this type can be
-/// considered an abstract interface.
+/// list of slots, where each can either push or pop a tree level. Each
+/// `Begin` slot also records the length of its element, so that skipping
+/// over a subtree while diffing is constant time, and the number of
+/// widgets it and its earlier siblings contribute to its parent. Applying a mutation, however,
+/// rebuilds the list and is linear in the size of the tree. But ideally
+/// implementation details are hidden, and this type can be considered an
+/// abstract interface.
 #[derive(Default)]
 pub struct Tree {
     slots: Vec<Slot>,
+    /// For each slot, the number of slots in the element beginning there,
+    /// including its `End`. This is 0 for `End` slots.
+    lens: Vec<usize>,
+    /// For each slot, the number of widgets the element beginning there
+    /// contributes to its parent: one for a view, otherwise those of its
+    /// children. This is 0 for `End` slots.
+    widgets: Vec<usize>,
+    /// For each slot, the number of widgets the earlier siblings of the
+    /// element beginning there contribute to its parent, so the widgets
+    /// between two siblings are counted in constant time. This is 0 for
+    /// `End` slots.
+    before: Vec<usize>,
 }
 
 #[derive(Debug)]
@@ -62,6 +87,59 @@ pub struct MutCursor<'a> {
     nest: usize,
     // Nesting level in old tree
     old_nest: usize,
+    // One frame for each open node of the old tree, including the root.
+    frames: Vec<Frame>,
+    // One set of sequence counters for each open node of the new tree,
+    // including the root.
+    seq: Vec<HashMap<(Caller, Option<UserKey>), usize>>,
+    // The ids of the open elements of the new tree.
+    open_ids: Vec<Id>,
+    // The id of the element most recently ended.
+    last_id: Option<Id>,
+    // The id of the element first ended.
+    first_id: Option<Id>,
+}
+
+/// The state of a node of the old tree while its children are visited.
+struct Frame {
+    /// The slot index of the node's `End`, or the tree length for the root.
+    end: usize,
+    /// Where to resume when the node ends, if it was moved.
+    resume: Option<usize>,
+    /// Total slots of children ahead of the cursor that were already moved.
+    moved_slots: usize,
+    /// The slot indices of children ahead of the cursor that were already
+    /// moved.
+    moved: HashSet<usize>,
+    /// The slot indices of children passed over, to be moved when their
+    /// keys come up.
+    deferred: BTreeSet<usize>,
+    /// The children from the cursor position on, built on the first miss.
+    index: Option<Index>,
+    /// The expected order of the children, if known.
+    plan: Option<Plan>,
+}
+
+/// The children of a node of the old tree, from where the cursor was when
+/// they were indexed.
+struct Index {
+    /// The slot index of each child, by key.
+    keys: HashMap<Key, usize>,
+    /// The slot index of each child, in order.
+    children: Vec<usize>,
+    /// The children moved so far, by position in `children`.
+    moved: Sums,
+}
+
+/// The expected order of children with keys from one call site, given by
+/// `MutCursor::expect_keys`.
+struct Plan {
+    /// The call site of the keys.
+    caller: Caller,
+    /// The slot indices of the children with the keys.
+    expected: HashSet<usize>,
+    /// The slot indices of those already in order, to be kept in place.
+    anchors: HashSet<usize>,
 }
 
 /// A tree mutation.
@@ -75,14 +153,28 @@ pub struct MutCursor<'a> {
 /// to the new tree being balanced:
 ///
 /// * The sum of Skip, Delete, and Update sizes equals the number of slots
-/// in the original tree.
+/// in the original tree. Slots of a moved element are counted once, by
+/// the items following the `Move`.
 /// * If a `Begin` is in a `Delete`, the delete covers all the way to the
 /// matching `End`.
 /// * If a `Begin` is in an `Insert`, the insert covers all the way to the
 /// matching `End`.
-/// * `Update` doesn't change the `Slot` variant.
+/// * `Update` doesn't change the `Slot` variant, nor whether the payload
+/// is a view.
+/// * A `Move` is only of an element of the same node not yet visited,
+/// either later in the node or passed over, and is followed by items
+/// covering all of that element. Only an element passed over may then be
+/// deleted.
+///
+/// These are checked by `validate`, which runs on each mutation produced by
+/// `MutCursor` or received by a `Renderer` when debug assertions are
+/// enabled, before any widget sees it.
 #[derive(Debug)]
-pub struct Mutation(Vec<MutationItem>);
+pub struct Mutation {
+    items: Vec<MutationItem>,
+    /// The original slot indices of all moved elements.
+    moved: HashSet<usize>,
+}
 
 /// One item in the internal representation of a tree mutation.
 #[derive(Debug)]
@@ -98,9 +190,35 @@ enum MutationItem {
     /// Update is similar to delete + insert, but is intended to
     /// preserve the identity of those tree locations.
     Update(Vec<Slot>),
+    /// Move an element of the node to the current location.
+    ///
+    /// `from` is the index of the element's first slot in the original
+    /// tree. The element is either later in the node, or was passed over:
+    /// its original location was reached earlier, without consuming it.
+    /// The items that follow apply to the moved element, and its slots
+    /// are then considered absent from their original location.
+    ///
+    /// `n` is the number of siblings between the current location and the
+    /// element, not counting those already moved: the siblings before it
+    /// if it is later in the node, the siblings after it if it was passed
+    /// over. An element passed over is ahead of the current location until
+    /// an item covers a sibling after it.
+    Move { from: usize, n: usize },
 }
 
 /// One item in a mutation for a single node.
+///
+/// Items are in terms of widgets. Elements that are not views, such as
+/// state nodes, are transparent: their children are reported in their
+/// place, as children of the nearest enclosing view.
+///
+/// A container applies the items in order, with an index into its
+/// children that starts at zero; `Flex` is an example. Reordered children
+/// are reported as `Move` or `MoveBack`, which a container must handle to
+/// keep its children in the order of the tree. More kinds of items may be
+/// added, so the enum is non-exhaustive.
+#[derive(Clone)]
+#[non_exhaustive]
 pub enum MutIterItem<'a> {
     /// No change for the next n children.
     Skip(usize),
@@ -112,13 +230,109 @@ pub enum MutIterItem<'a> {
     ///
     /// For discussion: include old + new values?
     Update(Option<&'a Payload>, MutationIter<'a>),
+    /// Move the child `n` positions ahead to the current location, then
+    /// update it.
+    ///
+    /// The moved child retains its identity, so there is no need to
+    /// rebuild it.
+    Move(usize, Option<&'a Payload>, MutationIter<'a>),
+    /// Move the child `n` positions behind the current location to the
+    /// current location.
+    ///
+    /// The child was skipped earlier, and is now the next child, so the
+    /// items that follow apply to it.
+    MoveBack(usize),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 /// An iterator for reading out a tree mutation.
 pub struct MutationIter<'a> {
+    tree: &'a Tree,
+    /// The elements being read, innermost last. All but the first are
+    /// transparent elements.
+    stack: Vec<Level<'a>>,
+    /// Items already determined but not yet returned.
+    pending: VecDeque<MutIterItem<'a>>,
+    /// The number of children before the current location.
+    done: usize,
+    /// The children skipped to be moved back later, by slot index.
+    deferred: HashMap<usize, Deferred>,
+    /// The number of widgets moved back of each child skipped, in the
+    /// order they were skipped.
+    recalled: Sums,
+}
+
+/// Where an iteration over the children of a widget starts, apart from
+/// the tree and the mutation, so it can be kept while they are borrowed
+/// and resumed later with `MutationIter::resume`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct IterPos(Option<(usize, usize, usize)>);
+
+/// A child skipped to be moved back later, while reading out a mutation.
+#[derive(Clone, Copy)]
+struct Deferred {
+    /// The location of its first widget when it was skipped.
+    at: usize,
+    /// The number of children skipped before it.
+    order: usize,
+    /// The widgets then moved back of the children skipped before it.
+    recalled: usize,
+}
+
+/// The children of one element, while reading out a mutation.
+#[derive(Clone)]
+struct Level<'a> {
+    elements: ElementIter<'a>,
+    /// If the element was moved, the offset of its remaining widgets
+    /// from the current location.
+    pull: Option<usize>,
+    /// If the element was moved, its original slot index, and the offset
+    /// of its widgets when the move began.
+    origin: Option<(usize, usize)>,
+    /// The original slot indices of children already moved, with the
+    /// number of their deleted widgets left behind at that location.
+    moved: HashMap<usize, usize>,
+    /// For each slot from the first child on, the widgets moved away
+    /// from that location, built on the first move.
+    moved_widgets: Option<Sums>,
+    /// The slot index of the first child.
+    start: usize,
+}
+
+/// Counts by index, with sums over a prefix in logarithmic time.
+///
+/// This is a Fenwick tree: the entry at each index holds the sum of the
+/// counts down to that index with its lowest set bit cleared, counting
+/// from one.
+#[derive(Clone, Default)]
+struct Sums(Vec<usize>);
+
+/// One item in a mutation, in terms of elements of a single node.
+enum ElementItem<'a> {
+    /// Skip the element at the given slot index.
+    Skip(usize),
+    /// Delete the element at the given slot index.
+    Delete(usize),
+    Insert(&'a Item, ElementIter<'a>),
+    /// Update the element, with the old and (if changed) new payloads.
+    Update(&'a Payload, Option<&'a Payload>, ElementIter<'a>),
+    /// Move the element at the given slot index, then update it.
+    Move(usize, &'a Payload, Option<&'a Payload>, ElementIter<'a>),
+    /// Move back the element at the given slot index, passed over earlier,
+    /// then apply the item to it.
+    Recalled(usize, Box<ElementItem<'a>>),
+    /// The original location of the moved element at the given slot
+    /// index has been reached. It was either moved already, or is passed
+    /// over, to be moved later.
+    Vacated(usize),
+}
+
+#[derive(Clone, Copy)]
+/// An iterator over the elements of a single node in a mutation.
+struct ElementIter<'a> {
     tree: &'a Tree,
     mutation: &'a [MutationItem],
+    moved: &'a HashSet<usize>,
     /// An index to the slot number in the tree.
     tree_ix: usize,
     /// An index to the item in the mutation.
@@ -133,61 +347,198 @@ pub struct MutationIter<'a> {
 
 impl Tree {
     /// Apply the mutation, mutating the tree.
+    ///
+    /// A mutation of only skips and updates, such as that of a run that
+    /// changes nothing or only some payloads, is applied in place, in time
+    /// linear in the number of items and updated slots. Otherwise, this
+    /// builds the new slots in a single pass, then recomputes the element
+    /// lengths and widget counts, in time linear in the size of the tree.
     pub fn mutate(&mut self, mutation: Mutation) {
-        // This implementation isn't trying to be efficient.
-        let mut ix = 0;
-        for mut_item in mutation.0 {
+        if mutation
+            .items
+            .iter()
+            .all(|item| matches!(item, MutationItem::Skip(_) | MutationItem::Update(_)))
+        {
+            self.update_in_place(mutation.items);
+            return;
+        }
+        let mut old = OldSlots {
+            slots: std::mem::take(&mut self.slots)
+                .into_iter()
+                .map(Some)
+                .collect(),
+            lens: std::mem::take(&mut self.lens),
+            moved: &mutation.moved,
+            ix: 0,
+            returns: Vec::new(),
+        };
+        // The first element may have been moved.
+        old.skip_moved();
+        let mut slots = Vec::with_capacity(old.slots.len());
+        for mut_item in mutation.items {
             match mut_item {
-                MutationItem::Skip(n) => ix += n,
+                MutationItem::Skip(n) => {
+                    for _ in 0..n {
+                        slots.push(old.take());
+                    }
+                }
                 MutationItem::Delete(n) => {
-                    self.slots.drain(ix..ix + n);
+                    for _ in 0..n {
+                        old.take();
+                    }
                 }
-                MutationItem::Insert(new) => {
-                    let n = new.len();
-                    self.slots.splice(ix..ix, new);
-                    ix += n;
+                MutationItem::Insert(new) => slots.extend(new),
+                MutationItem::Update(new) => {
+                    for slot in new {
+                        old.take();
+                        slots.push(slot);
+                    }
                 }
+                MutationItem::Move { from, .. } => old.jump(from),
+            }
+        }
+        let (lens, widgets, before) = compute_lens(&slots);
+        self.lens = lens;
+        self.widgets = widgets;
+        self.before = before;
+        self.slots = slots;
+    }
+
+    /// Apply skips and updates to the slots in place.
+    ///
+    /// An update keeps the variant of each slot and whether its payload is
+    /// a view, so the element lengths and widget counts stay the same.
+    fn update_in_place(&mut self, items: Vec<MutationItem>) {
+        let mut ix = 0;
+        for mut_item in items {
+            match mut_item {
+                MutationItem::Skip(n) => ix += n,
                 MutationItem::Update(new) => {
-                    let n = new.len();
-                    self.slots.splice(ix..ix + n, new);
-                    ix += n;
+                    for slot in new {
+                        self.slots[ix] = slot;
+                        ix += 1;
+                    }
+                }
+                _ => unreachable!("only skips and updates are applied in place"),
+            }
+        }
+    }
+}
+
+/// The slots of the old tree, taken in the order a mutation consumes them.
+struct OldSlots<'a> {
+    slots: Vec<Option<Slot>>,
+    lens: Vec<usize>,
+    moved: &'a HashSet<usize>,
+    ix: usize,
+    /// For each moved element being taken, its end and where to resume.
+    returns: Vec<(usize, usize)>,
+}
+
+impl<'a> OldSlots<'a> {
+    fn take(&mut self) -> Slot {
+        let slot = self.slots[self.ix].take().unwrap();
+        self.ix += 1;
+        self.skip_moved();
+        slot
+    }
+
+    /// Return from moved elements that are done, and skip those reached
+    /// at their original location.
+    fn skip_moved(&mut self) {
+        loop {
+            if let Some(&(end, resume)) = self.returns.last() {
+                if self.ix == end {
+                    self.returns.pop();
+                    self.ix = resume;
+                    continue;
                 }
             }
+            if self.moved.contains(&self.ix) {
+                // Taken at the location it was moved to.
+                self.ix += self.lens[self.ix];
+                continue;
+            }
+            return;
         }
     }
+
+    /// Continue taking slots from a moved element.
+    fn jump(&mut self, from: usize) {
+        self.returns.push((from + self.lens[from], self.ix));
+        self.ix = from;
+    }
+}
+
+/// The length of the element beginning at each slot, the number of
+/// widgets it contributes to its parent, and the number its earlier
+/// siblings contribute.
+fn compute_lens(slots: &[Slot]) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+    let mut lens = vec![0; slots.len()];
+    let mut widgets = vec![0; slots.len()];
+    let mut before = vec![0; slots.len()];
+    // The open elements, with the widgets of their children so far.
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    // The widgets of the top-level elements so far.
+    let mut top = 0;
+    for (ix, slot) in slots.iter().enumerate() {
+        match slot {
+            Slot::Begin(_) => {
+                before[ix] = stack.last().map_or(top, |parent| parent.1);
+                stack.push((ix, 0));
+            }
+            Slot::End => {
+                let (begin, children) = stack.pop().unwrap();
+                lens[begin] = ix + 1 - begin;
+                let n = match &slots[begin] {
+                    Slot::Begin(item) if item.body.is_widget() => 1,
+                    _ => children,
+                };
+                widgets[begin] = n;
+                match stack.last_mut() {
+                    Some(parent) => parent.1 += n,
+                    None => top += n,
+                }
+            }
+        }
+    }
+    (lens, widgets, before)
 }
 
 impl Mutation {
     fn new() -> Mutation {
-        Mutation(Vec::new())
+        Mutation {
+            items: Vec::new(),
+            moved: HashSet::new(),
+        }
     }
 
     fn skip(&mut self, n: usize) {
         if n > 0 {
-            if let Some(MutationItem::Skip(old_n)) = self.0.last_mut() {
+            if let Some(MutationItem::Skip(old_n)) = self.items.last_mut() {
                 *old_n += n;
             } else {
-                self.0.push(MutationItem::Skip(n));
+                self.items.push(MutationItem::Skip(n));
             }
         }
     }
 
     fn delete(&mut self, n: usize) {
         if n > 0 {
-            if let Some(MutationItem::Delete(old_n)) = self.0.last_mut() {
+            if let Some(MutationItem::Delete(old_n)) = self.items.last_mut() {
                 *old_n += n;
             } else {
-                self.0.push(MutationItem::Delete(n));
+                self.items.push(MutationItem::Delete(n));
             }
         }
     }
 
     fn insert(&mut self, new: Vec<Slot>) {
         if !new.is_empty() {
-            if let Some(MutationItem::Insert(old)) = self.0.last_mut() {
+            if let Some(MutationItem::Insert(old)) = self.items.last_mut() {
                 old.extend(new);
             } else {
-                self.0.push(MutationItem::Insert(new));
+                self.items.push(MutationItem::Insert(new));
             }
         }
     }
@@ -203,10 +554,10 @@ impl Mutation {
 
     fn update(&mut self, new: Vec<Slot>) {
         if !new.is_empty() {
-            if let Some(MutationItem::Update(old)) = self.0.last_mut() {
+            if let Some(MutationItem::Update(old)) = self.items.last_mut() {
                 old.extend(new);
             } else {
-                self.0.push(MutationItem::Update(new));
+                self.items.push(MutationItem::Update(new));
             }
         }
     }
@@ -219,44 +570,129 @@ impl Mutation {
         // Just punt for now :)
         self.update(vec![slot]);
     }
+
+    fn move_element(&mut self, from: usize, n: usize) {
+        self.items.push(MutationItem::Move { from, n });
+        self.moved.insert(from);
+    }
+
+    /// Convert to the wire format, for sending to a renderer.
+    pub fn to_wire(&self) -> WireMutation {
+        let slots = |slots: &[Slot]| slots.iter().map(Slot::to_wire).collect();
+        let items = self
+            .items
+            .iter()
+            .map(|item| match item {
+                MutationItem::Skip(n) => WireItem::Skip(*n),
+                MutationItem::Delete(n) => WireItem::Delete(*n),
+                MutationItem::Insert(new) => WireItem::Insert(slots(new)),
+                MutationItem::Update(new) => WireItem::Update(slots(new)),
+                MutationItem::Move { from, n } => WireItem::Move { from: *from, n: *n },
+            })
+            .collect();
+        WireMutation { items }
+    }
+
+    /// Convert from the wire format.
+    ///
+    /// The result applies to a tree built from the same sequence of wire
+    /// mutations. Keys are not sent, so that tree can only be mutated
+    /// this way, not by running app logic on it.
+    pub fn from_wire(wire: WireMutation) -> Mutation {
+        let slots = |slots: Vec<WireSlot>| slots.into_iter().map(Slot::from_wire).collect();
+        let mut mutation = Mutation::new();
+        for item in wire.items {
+            match item {
+                WireItem::Skip(n) => mutation.items.push(MutationItem::Skip(n)),
+                WireItem::Delete(n) => mutation.items.push(MutationItem::Delete(n)),
+                WireItem::Insert(new) => mutation.items.push(MutationItem::Insert(slots(new))),
+                WireItem::Update(new) => mutation.items.push(MutationItem::Update(slots(new))),
+                WireItem::Move { from, n } => mutation.move_element(from, n),
+            }
+        }
+        mutation
+    }
+}
+
+impl Slot {
+    fn to_wire(&self) -> WireSlot {
+        match self {
+            Slot::Begin(item) => {
+                let body = match &item.body {
+                    Payload::View(view) => WirePayload::View(ViewData::from_view(view.as_ref())),
+                    _ => WirePayload::Transparent,
+                };
+                WireSlot::Begin { id: item.id, body }
+            }
+            Slot::End => WireSlot::End,
+        }
+    }
+
+    fn from_wire(slot: WireSlot) -> Slot {
+        match slot {
+            WireSlot::Begin { id, body } => {
+                let body = match body {
+                    WirePayload::Transparent => Payload::Placeholder,
+                    WirePayload::View(data) => Payload::View(data.into_view()),
+                };
+                Slot::Begin(Item {
+                    key: Key::null(),
+                    id,
+                    body,
+                })
+            }
+            WireSlot::End => Slot::End,
+        }
+    }
 }
 
 impl<'a> MutCursor<'a> {
     /// Start building a tree mutation.
     pub fn new(tree: &Tree) -> MutCursor {
+        let root = Frame::new(tree.slots.len(), None);
         MutCursor {
             tree,
             ix: 0,
             mutation: Mutation::new(),
             nest: 0,
             old_nest: 0,
+            frames: vec![root],
+            seq: vec![HashMap::new()],
+            open_ids: Vec::new(),
+            last_id: None,
+            first_id: None,
         }
     }
 
     /// Begin an element.
     #[track_caller]
     pub fn begin(&mut self, body: Payload) {
-        let caller = Location::caller().into();
-        let key = Key::new(caller, self.seq_ix(caller));
+        let key = self.key_from_loc(Location::caller());
         self.begin_internal(key, body);
     }
 
     /// Add a leaf element.
     #[track_caller]
     pub fn leaf(&mut self, body: Payload) {
-        let caller = Location::caller().into();
-        let key = Key::new(caller, self.seq_ix(caller));
+        let key = self.key_from_loc(Location::caller());
         self.begin_internal(key, body);
         self.end();
     }
 
     pub(crate) fn begin_loc(&mut self, body: Payload, loc: &'static Location) -> Id {
-        self.begin_internal(self.key_from_loc(loc), body)
+        let key = self.key_from_loc(loc);
+        self.begin_internal(key, body)
     }
 
-    pub(crate) fn key_from_loc(&self, loc: &'static Location) -> Key {
+    pub(crate) fn key_from_loc(&mut self, loc: &'static Location) -> Key {
         let caller = loc.into();
-        Key::new(caller, self.seq_ix(caller))
+        Key::new(caller, self.seq_ix(caller, None))
+    }
+
+    pub(crate) fn key_from_loc_user(&mut self, loc: &'static Location, user: UserKey) -> Key {
+        let caller = loc.into();
+        let seq_ix = self.seq_ix(caller, Some(user.clone()));
+        Key::with_user_key(caller, seq_ix, user)
     }
 
     /// Begin a new element, using a callback.
@@ -273,31 +709,47 @@ impl<'a> MutCursor<'a> {
     where
         F: FnOnce(Id, Option<&Payload>) -> (Option<Payload>, T),
     {
+        self.seq.push(HashMap::new());
         if self.nest == self.old_nest {
-            // TODO: really should have fast path if the key matches
-            if let Some(n) = self.find_key(key) {
-                self.ix += n;
-                self.mutation.delete(n);
-                if let Some(Slot::Begin(old)) = self.tree.slots.get(self.ix) {
-                    if old.key == key {
-                        self.ix += 1;
-                        self.nest += 1;
-                        self.old_nest += 1;
-                        let id = old.id;
-                        let (new_body, result) = f(id, Some(&old.body));
-                        if let Some(body) = new_body {
-                            let item = Item { key, id, body };
-                            self.mutation.update_one(Slot::Begin(item));
-                        } else {
-                            self.mutation.skip(1);
-                        }
-                        return result;
+            if let Some(from) = self.find_key(&key) {
+                let mut resume = None;
+                let plan = self.frames.last().unwrap().plan.as_ref();
+                if from > self.ix && plan.map_or(false, |plan| plan.anchors.contains(&from)) {
+                    // Found later in this node, but in order; keep it in
+                    // place, passing over the elements before it.
+                    self.pass_over(from);
+                } else if from != self.ix {
+                    // Move it here, and come back once it's done.
+                    let frame = self.frames.last_mut().unwrap();
+                    let n = frame.index.as_mut().unwrap().move_child(self.ix, from);
+                    self.mutation.move_element(from, n);
+                    if from > self.ix {
+                        frame.moved.insert(from);
+                        frame.moved_slots += self.tree.lens[from];
+                    } else {
+                        frame.deferred.remove(&from);
+                    }
+                    resume = Some(self.ix);
+                    self.ix = from;
+                }
+                if let Slot::Begin(old) = &self.tree.slots[self.ix] {
+                    self.enter(resume);
+                    let id = old.id;
+                    self.open_ids.push(id);
+                    let (new_body, result) = f(id, Some(&old.body));
+                    if let Some(body) = new_body {
+                        let item = Item { key, id, body };
+                        self.mutation.update_one(Slot::Begin(item));
+                    } else {
+                        self.mutation.skip(1);
                     }
+                    return result;
                 }
             }
         }
         self.nest += 1;
         let id = Id::new();
+        self.open_ids.push(id);
         let (body, result) = f(id, None);
         let body = body.expect("must provide new payload on insert");
         let item = Item { key, id, body };
@@ -319,13 +771,30 @@ impl<'a> MutCursor<'a> {
         })
     }
 
+    /// Enter the element of the old tree at the cursor.
+    fn enter(&mut self, resume: Option<usize>) {
+        let end = self.ix + self.tree.lens[self.ix] - 1;
+        self.frames.push(Frame::new(end, resume));
+        self.ix += 1;
+        self.nest += 1;
+        self.old_nest += 1;
+    }
+
     /// End an element.
     pub fn end(&mut self) {
+        self.seq.pop();
+        self.last_id = self.open_ids.pop();
+        self.first_id = self.first_id.or(self.last_id);
         if self.nest == self.old_nest {
+            self.settle_deferred(false);
             let n_trim = self.count_trim();
-            self.ix += n_trim + 1;
             self.mutation.delete(n_trim);
             self.mutation.skip(1);
+            let frame = self.frames.pop().unwrap();
+            self.ix = frame.resume.unwrap_or(frame.end + 1);
+            self.nest -= 1;
+            self.old_nest -= 1;
+            self.skip_moved();
         } else {
             self.nest -= 1;
             self.mutation.insert_one(Slot::End);
@@ -337,6 +806,22 @@ impl<'a> MutCursor<'a> {
         if let Some(cur_slots) = self.tree.count_slots(self.ix) {
             self.ix += cur_slots;
             self.mutation.skip(cur_slots);
+            self.skip_moved();
+        }
+    }
+
+    /// Skip the remaining elements of the current node.
+    ///
+    /// Elements passed over are kept too, at the current location.
+    pub fn skip_rest(&mut self) {
+        if self.nest == self.old_nest {
+            self.settle_deferred(true);
+            let n_rest = self.count_trim();
+            self.mutation.skip(n_rest);
+            let frame = self.frames.last_mut().unwrap();
+            frame.moved.clear();
+            frame.moved_slots = 0;
+            self.ix = frame.end;
         }
     }
 
@@ -345,6 +830,87 @@ impl<'a> MutCursor<'a> {
         if let Some(cur_slots) = self.tree.count_slots(self.ix) {
             self.ix += cur_slots;
             self.mutation.delete(cur_slots);
+            self.skip_moved();
+        }
+    }
+
+    /// Expect elements with the given keys to be begun next, in order.
+    ///
+    /// Elements are matched by key wherever they are in the node. Without
+    /// this, an element found later is moved to the cursor, so moving one
+    /// element back means moving each element it passed instead. With the
+    /// keys known up front, a longest run of the elements still in order
+    /// stays in place, and only the others are moved. The keys should be
+    /// unique, and come from a single call site.
+    pub(crate) fn expect_keys(&mut self, keys: &[Key]) {
+        if self.nest != self.old_nest || keys.is_empty() {
+            return;
+        }
+        let ix = self.ix;
+        let tree = self.tree;
+        let frame = self.frames.last_mut().unwrap();
+        let index = frame.index.get_or_insert_with(|| Index::new(tree, ix));
+        let moved = &frame.moved;
+        // The slot indices of the elements not yet visited, in the new order.
+        let froms: Vec<usize> = keys
+            .iter()
+            .filter_map(|key| index.keys.get(key).copied())
+            .filter(|&from| from >= ix && !moved.contains(&from))
+            .collect();
+        frame.plan = Some(Plan {
+            caller: keys[0].caller,
+            anchors: longest_increasing(&froms).into_iter().collect(),
+            expected: froms.into_iter().collect(),
+        });
+    }
+
+    /// Pass over the elements before the slot index `until`.
+    ///
+    /// Elements from the call site of the expected keys whose keys are not
+    /// expected are deleted. Others are deferred: they are moved when their
+    /// keys come up, or deleted when the node ends.
+    fn pass_over(&mut self, until: usize) {
+        while self.ix < until {
+            let len = self.tree.lens[self.ix];
+            let frame = self.frames.last_mut().unwrap();
+            let plan = frame.plan.as_ref().unwrap();
+            let gone = !plan.expected.contains(&self.ix)
+                && matches!(&self.tree.slots[self.ix], Slot::Begin(item) if item.key.caller == plan.caller);
+            if gone {
+                self.mutation.delete(len);
+            } else {
+                frame.deferred.insert(self.ix);
+            }
+            self.ix += len;
+            self.skip_moved();
+        }
+    }
+
+    /// Move the elements passed over and not moved since to the cursor,
+    /// then keep or delete them.
+    fn settle_deferred(&mut self, keep: bool) {
+        let frame = self.frames.last_mut().unwrap();
+        for from in std::mem::take(&mut frame.deferred) {
+            let len = self.tree.lens[from];
+            let n = frame.index.as_mut().unwrap().move_child(self.ix, from);
+            self.mutation.move_element(from, n);
+            if keep {
+                self.mutation.skip(len);
+            } else {
+                self.mutation.delete(len);
+            }
+        }
+    }
+
+    /// Step over elements that have already been moved.
+    ///
+    /// Their slots were accounted for at the location they were moved to.
+    fn skip_moved(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+        while frame.moved.remove(&self.ix) {
+            let len = self.tree.lens[self.ix];
+            frame.moved_slots -= len;
+            self.ix += len;
         }
     }
 
@@ -352,9 +918,11 @@ impl<'a> MutCursor<'a> {
     ///
     /// This works without any regard to keys.
     pub fn begin_insert(&mut self, body: Payload) {
+        self.seq.push(HashMap::new());
         self.nest += 1;
         let key = Key::null();
         let id = Id::new();
+        self.open_ids.push(id);
         let item = Item { key, id, body };
         self.mutation.insert_one(Slot::Begin(item));
     }
@@ -366,13 +934,13 @@ impl<'a> MutCursor<'a> {
     pub fn begin_update(&mut self, body: Payload) {
         assert_eq!(self.nest, self.old_nest);
         if let Some(Slot::Begin(old)) = self.tree.slots.get(self.ix) {
-            self.ix += 1;
-            self.nest += 1;
-            self.old_nest += 1;
+            self.seq.push(HashMap::new());
+            self.enter(None);
+            self.open_ids.push(old.id);
             if old.body == body {
                 self.mutation.skip(1);
             } else {
-                let key = old.key;
+                let key = old.key.clone();
                 let id = old.id;
                 let item = Item { key, id, body };
                 self.mutation.update_one(Slot::Begin(item));
@@ -382,86 +950,218 @@ impl<'a> MutCursor<'a> {
         }
     }
 
+    /// The id of the element most recently ended.
+    pub fn last_id(&self) -> Option<Id> {
+        self.last_id
+    }
+
+    /// The id of the element first ended.
+    pub(crate) fn first_id(&self) -> Option<Id> {
+        self.first_id
+    }
+
     /// Reap the mutation.
     pub fn into_mutation(mut self) -> Mutation {
-        let n_trim = self.tree.slots.len() - self.ix;
+        self.settle_deferred(false);
+        let n_trim = self.count_trim();
         self.mutation.delete(n_trim);
+        self.mutation.assert_valid(self.tree);
         self.mutation
     }
 
     /// Find the key in the current node.
     ///
-    /// Returns number of slots until the key.
-    fn find_key(&self, key: Key) -> Option<usize> {
-        let mut nest = 0;
-        let mut ix = self.ix;
-        while ix < self.tree.slots.len() {
-            match &self.tree.slots[ix] {
-                Slot::Begin(slot) => {
-                    if nest == 0 && slot.key == key {
-                        return Some(ix - self.ix);
-                    }
-                    nest += 1;
-                }
-                Slot::End => {
-                    if nest == 0 {
-                        return None;
-                    }
-                    nest -= 1;
-                }
-            }
-            ix += 1;
+    /// Returns the slot index of the element, which is at the cursor,
+    /// later in the node, or passed over.
+    fn find_key(&mut self, key: &Key) -> Option<usize> {
+        let ix = self.ix;
+        let tree = self.tree;
+        let frame = self.frames.last_mut().unwrap();
+        match &tree.slots.get(ix) {
+            Some(Slot::Begin(item)) if &item.key == key => return Some(ix),
+            Some(Slot::Begin(_)) => (),
+            _ if frame.deferred.is_empty() => return None,
+            _ => (),
+        }
+        let index = frame.index.get_or_insert_with(|| Index::new(tree, ix));
+        let from = *index.keys.get(key)?;
+        if (from >= ix && !frame.moved.contains(&from)) || frame.deferred.contains(&from) {
+            Some(from)
+        } else {
+            // Already visited.
+            None
         }
-        None
     }
 
-    /// The number of previous items in this node with this caller.
-    fn seq_ix(&self, caller: Caller) -> usize {
-        let mut seq_ix = 0;
-        let mut nest = 0;
-        let mut ix = self.ix;
-        while ix > 0 {
-            ix -= 1;
-            match &self.tree.slots[ix] {
-                Slot::End => nest += 1,
-                Slot::Begin(slot) => {
-                    if nest == 0 {
-                        break;
-                    } else if nest == 1 && slot.key.caller == caller {
-                        seq_ix += 1;
-                    }
-                    nest -= 1;
-                }
-            }
-        }
+    /// The number of previous elements in this node with this caller and
+    /// user key, counting up.
+    fn seq_ix(&mut self, caller: Caller, user: Option<UserKey>) -> usize {
+        let count = self
+            .seq
+            .last_mut()
+            .unwrap()
+            .entry((caller, user))
+            .or_default();
+        let seq_ix = *count;
+        *count += 1;
         seq_ix
     }
 
-    /// The number of slots until the end of the current node.
+    /// The number of slots until the end of the current node, not
+    /// counting elements that have already been moved.
     fn count_trim(&self) -> usize {
-        let mut nest = 0usize;
-        let mut ix = self.ix;
-        loop {
-            match self.tree.slots[ix] {
-                Slot::Begin(_) => nest += 1,
-                Slot::End => {
-                    if nest == 0 {
-                        return ix - self.ix;
-                    }
-                    nest -= 1;
-                }
-            }
-            ix += 1;
-        }
+        let frame = self.frames.last().unwrap();
+        frame.end - self.ix - frame.moved_slots
     }
 
-    /// The ids of all nodes under the current node.
+    /// The ids of the children of the current node not yet visited,
+    /// including those passed over, and of all their descendants.
+    ///
+    /// A node that is being inserted has none.
     pub(crate) fn descendant_ids(&self) -> impl Iterator<Item = Id> + '_ {
-        self.tree.descendant_ids(self.ix)
+        let tree = self.tree;
+        let (end, deferred) = if self.nest == self.old_nest {
+            let frame = self.frames.last().unwrap();
+            (frame.end, Some(&frame.deferred))
+        } else {
+            (self.ix, None)
+        };
+        let deferred = deferred
+            .into_iter()
+            .flatten()
+            .flat_map(move |&from| tree.ids_between(from, from + tree.lens[from]));
+        tree.ids_between(self.ix, end).chain(deferred)
+    }
+}
+
+impl Frame {
+    fn new(end: usize, resume: Option<usize>) -> Frame {
+        Frame {
+            end,
+            resume,
+            moved_slots: 0,
+            moved: HashSet::new(),
+            deferred: BTreeSet::new(),
+            index: None,
+            plan: None,
+        }
+    }
+}
+
+impl Index {
+    /// Index the elements from `ix` to the end of the node.
+    fn new(tree: &Tree, mut ix: usize) -> Index {
+        let mut keys = HashMap::new();
+        let mut children = Vec::new();
+        while let Some(Slot::Begin(item)) = tree.slots.get(ix) {
+            keys.entry(item.key.clone()).or_insert(ix);
+            children.push(ix);
+            ix += tree.lens[ix];
+        }
+        let moved = Sums::new(children.len());
+        Index {
+            keys,
+            children,
+            moved,
+        }
+    }
+
+    /// The position in `children` of the child at the slot index, or of
+    /// the end of the node.
+    fn position(&self, ix: usize) -> usize {
+        match self.children.binary_search(&ix) {
+            Ok(pos) | Err(pos) => pos,
+        }
+    }
+
+    /// Record that the child at `from` is moved to the cursor at `ix`.
+    ///
+    /// Returns the number of siblings between them not moved before, as
+    /// recorded in the `Move`.
+    fn move_child(&mut self, ix: usize, from: usize) -> usize {
+        let (at, pos) = (self.position(ix), self.position(from));
+        let moved_between =
+            |lo: usize, hi: usize| self.moved.sum_before(hi) - self.moved.sum_before(lo);
+        let n = if pos >= at {
+            pos - at - moved_between(at, pos)
+        } else {
+            at - pos - 1 - moved_between(pos + 1, at)
+        };
+        self.moved.add(pos, 1);
+        n
+    }
+}
+
+/// A longest strictly increasing subsequence of the values.
+///
+/// This takes time `O(n log n)`, by keeping, for each length, the run of
+/// that length with the smallest last value.
+///
+/// Keeping the longest run in place is what makes the moves minimal, and
+/// finding it needs `O(n log n)` comparisons in general. Linear schemes,
+/// such as keeping each row that comes after the last one kept, move up
+/// to `n - 1` rows when one row moves to the front.
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // For each length, the index of the last value of the best run.
+    let mut tails: Vec<usize> = Vec::new();
+    // For each index, the index of the previous value in its run.
+    let mut prev = vec![None; values.len()];
+    for (i, &value) in values.iter().enumerate() {
+        let len = match tails.binary_search_by(|&t| values[t].cmp(&value)) {
+            Ok(len) | Err(len) => len,
+        };
+        if len > 0 {
+            prev[i] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
     }
+    let mut run = Vec::with_capacity(tails.len());
+    let mut cur = tails.last().copied();
+    while let Some(i) = cur {
+        run.push(values[i]);
+        cur = prev[i];
+    }
+    run.reverse();
+    run
 }
 
 impl Tree {
+    /// The id and payload of each element, in tree order.
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &Payload)> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Begin(item) => Some((item.id, &item.body)),
+            Slot::End => None,
+        })
+    }
+
+    /// A structured snapshot of the tree.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            nodes: self.snapshot_children(0, self.slots.len()),
+        }
+    }
+
+    /// Snapshots of the elements between the slot indices.
+    fn snapshot_children(&self, mut ix: usize, end: usize) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while ix < end {
+            match &self.slots[ix] {
+                Slot::Begin(item) => {
+                    let len = self.lens[ix];
+                    let children = self.snapshot_children(ix + 1, ix + len - 1);
+                    nodes.push(Node::new(&item.key, item.id, &item.body, children));
+                    ix += len;
+                }
+                Slot::End => panic!("unbalanced tree at slot {}", ix),
+            }
+        }
+        nodes
+    }
+
     pub fn dump(&self) {
         let mut nest = 0;
         for slot in &self.slots {
@@ -479,22 +1179,39 @@ impl Tree {
     ///
     /// Returns `None` if there is no element starting at `ix`.
     fn count_slots(&self, ix: usize) -> Option<usize> {
-        if ix == self.slots.len() {
-            return None;
+        match self.lens.get(ix) {
+            Some(&n) if n > 0 => Some(n),
+            _ => None,
         }
-        if let Slot::End = self.slots[ix] {
-            None
+    }
+
+    /// The payload of the element starting at `ix`.
+    fn body(&self, ix: usize) -> &Payload {
+        match &self.slots[ix] {
+            Slot::Begin(item) => &item.body,
+            Slot::End => panic!("no element at slot {}", ix),
+        }
+    }
+
+    /// The number of widgets the element starting at `ix` contributes
+    /// to its parent: one for a view, otherwise those of its children.
+    fn widget_count(&self, ix: usize) -> usize {
+        self.widgets[ix]
+    }
+
+    /// The slot index just past the children of the element whose first
+    /// child is at `start`, or the tree length for the top level.
+    fn children_end(&self, start: usize) -> usize {
+        if start == 0 {
+            self.slots.len()
         } else {
-            Some(count_slots(&self.slots[ix..]))
+            start - 1 + self.lens[start - 1] - 1
         }
     }
 
-    /// The ids of all nodes in the element starting at `ix`.
-    ///
-    /// This returns 0 if there is no element starting at `ix`.
-    fn descendant_ids(&self, ix: usize) -> impl Iterator<Item = Id> + '_ {
-        let n = self.count_slots(ix).unwrap_or(0);
-        self.slots[ix..ix + n].iter().filter_map(|slot| {
+    /// The ids of all elements beginning between the slot indices.
+    fn ids_between(&self, ix: usize, end: usize) -> impl Iterator<Item = Id> + '_ {
+        self.slots[ix..end].iter().filter_map(|slot| {
             if let Slot::Begin(b) = slot {
                 Some(b.id)
             } else {
@@ -526,32 +1243,402 @@ impl<'a> Iterator for MutationIter<'a> {
     type Item = MutIterItem<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        //println!("next tree_ix={} mut_ix={} consumed={}", self.tree_ix, self.mut_ix, self.consumed);
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            let level = self.stack.last_mut()?;
+            match level.elements.next() {
+                Some(item) => {
+                    let pull = level.pull;
+                    self.element(item, pull);
+                }
+                None => {
+                    let finished = self.stack.pop().unwrap();
+                    if let Some(parent) = self.stack.last_mut() {
+                        parent.finish_child(&finished);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> MutationIter<'a> {
+    /// Start an iteration over a mutation.
+    pub fn new(tree: &'a Tree, mutation: &'a Mutation) -> MutationIter<'a> {
+        let elements = ElementIter {
+            tree,
+            mutation: &mutation.items,
+            moved: &mutation.moved,
+            tree_ix: 0,
+            mut_ix: 0,
+            consumed: 0,
+        };
+        MutationIter::with_stack(tree, vec![Level::new(elements, None, None)])
+    }
+
+    fn with_stack(tree: &'a Tree, stack: Vec<Level<'a>>) -> MutationIter<'a> {
+        MutationIter {
+            tree,
+            stack,
+            pending: VecDeque::new(),
+            done: 0,
+            deferred: HashMap::new(),
+            recalled: Sums::default(),
+        }
+    }
+
+    /// The position of an iteration over the children of a widget, as
+    /// given with an item, before it is started.
+    pub(crate) fn pos(&self) -> IterPos {
+        debug_assert!(self.pending.is_empty() && self.stack.len() <= 1);
+        IterPos(self.stack.first().map(|level| {
+            let elements = &level.elements;
+            (elements.tree_ix, elements.mut_ix, elements.consumed)
+        }))
+    }
+
+    /// Resume an iteration over the children of a widget at the position,
+    /// with the tree and mutation it was taken from.
+    pub(crate) fn resume(tree: &'a Tree, mutation: &'a Mutation, pos: IterPos) -> MutationIter<'a> {
+        let stack = match pos.0 {
+            Some((tree_ix, mut_ix, consumed)) => {
+                let elements = ElementIter {
+                    tree,
+                    mutation: &mutation.items,
+                    moved: &mutation.moved,
+                    tree_ix,
+                    mut_ix,
+                    consumed,
+                };
+                vec![Level::new(elements, None, None)]
+            }
+            None => Vec::new(),
+        };
+        MutationIter::with_stack(tree, stack)
+    }
+
+    /// An iteration over the children of a widget.
+    fn child(&self, elements: ElementIter<'a>) -> MutationIter<'a> {
+        MutationIter::with_stack(self.tree, vec![Level::new(elements, None, None)])
+    }
+
+    /// An iteration with no items, for a widget that doesn't change.
+    fn empty(&self) -> MutationIter<'a> {
+        MutationIter::with_stack(self.tree, Vec::new())
+    }
+
+    /// Queue the items for an element of the innermost level.
+    ///
+    /// If `pull` is set, the element is part of a moved element, whose
+    /// remaining widgets are at that offset from the current location.
+    fn element(&mut self, item: ElementItem<'a>, pull: Option<usize>) {
+        let tree = self.tree;
+        match item {
+            ElementItem::Skip(ix) => {
+                let n = tree.widget_count(ix);
+                if let Some(offset) = pull {
+                    for _ in 0..n {
+                        let item = MutIterItem::Move(offset, None, self.empty());
+                        self.push(item);
+                    }
+                } else if n > 0 {
+                    self.push(MutIterItem::Skip(n));
+                }
+            }
+            ElementItem::Delete(ix) => {
+                let n = tree.widget_count(ix);
+                if let Some(offset) = pull {
+                    // Leave the widgets where they are, to be deleted when
+                    // the original location of the moved element is reached.
+                    self.level().pull = Some(offset + n);
+                } else if n > 0 {
+                    self.push(MutIterItem::Delete(n));
+                }
+            }
+            ElementItem::Insert(item, elements) => {
+                if item.body.is_widget() {
+                    let child_iter = self.child(elements);
+                    self.push(MutIterItem::Insert(item.id, &item.body, child_iter));
+                } else {
+                    self.stack.push(Level::new(elements, pull, None));
+                }
+            }
+            ElementItem::Update(old_body, body, elements) => {
+                if old_body.is_widget() {
+                    let child_iter = self.child(elements);
+                    self.push(match pull {
+                        Some(offset) => MutIterItem::Move(offset, body, child_iter),
+                        None => MutIterItem::Update(body, child_iter),
+                    });
+                } else {
+                    self.stack.push(Level::new(elements, pull, None));
+                }
+            }
+            ElementItem::Move(from, old_body, body, elements) => {
+                let level = self.level();
+                let offset = pull.unwrap_or(0) + level.widgets_before(from);
+                level.move_child(from);
+                if old_body.is_widget() {
+                    let child_iter = self.child(elements);
+                    self.push(MutIterItem::Move(offset, body, child_iter));
+                } else {
+                    self.stack
+                        .push(Level::new(elements, Some(offset), Some((from, offset))));
+                }
+            }
+            ElementItem::Recalled(from, item) => {
+                self.recall(from);
+                // Its widgets are now at the current location.
+                self.element(*item, None);
+            }
+            ElementItem::Vacated(from) => match self.level().take_moved(from) {
+                Some(left) => {
+                    if let Some(offset) = pull {
+                        self.level().pull = Some(offset + left);
+                    } else if left > 0 {
+                        self.push(MutIterItem::Delete(left));
+                    }
+                }
+                None => {
+                    // Passed over; skip its widgets until it is moved.
+                    self.defer(from);
+                    self.element(ElementItem::Skip(from), pull);
+                }
+            },
+        }
+    }
+
+    /// The innermost level.
+    fn level(&mut self) -> &mut Level<'a> {
+        self.stack.last_mut().unwrap()
+    }
+
+    /// Queue an item, keeping count of the children before the current
+    /// location.
+    fn push(&mut self, item: MutIterItem<'a>) {
+        match &item {
+            MutIterItem::Skip(n) => self.done += n,
+            MutIterItem::Delete(_) => (),
+            MutIterItem::Insert(..) | MutIterItem::Update(..) | MutIterItem::Move(..) => {
+                self.done += 1
+            }
+            MutIterItem::MoveBack(_) => self.done -= 1,
+        }
+        self.pending.push_back(item);
+    }
+
+    /// Note that the widgets of the element at the slot index `from` are
+    /// about to be skipped, to be moved back later.
+    fn defer(&mut self, from: usize) {
+        let order = self.recalled.len();
+        self.recalled.push();
+        let deferred = Deferred {
+            at: self.done,
+            order,
+            recalled: self.recalled.sum_before(order),
+        };
+        self.deferred.insert(from, deferred);
+    }
+
+    /// Queue the items moving back the widgets of the element at the slot
+    /// index `from`, skipped earlier.
+    fn recall(&mut self, from: usize) {
+        let deferred = self.deferred.remove(&from).unwrap();
+        let n = self.tree.widget_count(from);
+        // Widgets moved back since from before its own have shifted them.
+        let shift = self.recalled.sum_before(deferred.order) - deferred.recalled;
+        let behind = self.done - (deferred.at - shift) - n;
+        // Each moves the last remaining widget, so the offset stays.
+        for _ in 0..n {
+            self.push(MutIterItem::MoveBack(behind));
+        }
+        self.recalled.add(deferred.order, n);
+    }
+}
+
+impl<'a> Level<'a> {
+    fn new(
+        elements: ElementIter<'a>,
+        pull: Option<usize>,
+        origin: Option<(usize, usize)>,
+    ) -> Level<'a> {
+        Level {
+            elements,
+            pull,
+            origin,
+            moved: HashMap::new(),
+            moved_widgets: None,
+            start: elements.tree_ix,
+        }
+    }
+
+    /// The number of widgets in the children between the current location
+    /// and the slot index `from`.
+    ///
+    /// Children already moved only count the widgets left behind.
+    fn widgets_before(&self, from: usize) -> usize {
+        let tree = self.elements.tree;
+        let ix = self.elements.tree_ix;
+        let n = tree.before[from] - tree.before[ix];
+        match &self.moved_widgets {
+            Some(moved) => {
+                n - (moved.sum_before(from - self.start) - moved.sum_before(ix - self.start))
+            }
+            None => n,
+        }
+    }
+
+    /// Note that the child at the slot index `from` is moved.
+    fn move_child(&mut self, from: usize) {
+        let tree = self.elements.tree;
+        let start = self.start;
+        let moved = self
+            .moved_widgets
+            .get_or_insert_with(|| Sums::new(tree.children_end(start) - start));
+        moved.add(from - start, tree.widget_count(from));
+        self.moved.insert(from, 0);
+    }
+
+    /// Account for a transparent child that is done.
+    fn finish_child(&mut self, child: &Level) {
+        if let Some((from, offset)) = child.origin {
+            let left = child.pull.unwrap() - offset;
+            self.moved.insert(from, left);
+            if let Some(moved) = &mut self.moved_widgets {
+                moved.sub(from - self.start, left);
+            }
+        } else if child.pull.is_some() {
+            // Part of this moved element, so its deletes are left behind
+            // with ours. A child moved back is in place.
+            self.pull = child.pull;
+        }
+    }
+
+    /// Forget a moved child, returning the number of widgets left behind,
+    /// or `None` if it is passed over, to be moved later.
+    fn take_moved(&mut self, from: usize) -> Option<usize> {
+        self.moved.remove(&from)
+    }
+}
+
+impl Sums {
+    /// Zero counts at `len` indices.
+    fn new(len: usize) -> Sums {
+        Sums(vec![0; len])
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Add an index at the end, with a count of zero.
+    fn push(&mut self) {
+        let i = self.0.len() + 1;
+        let sum = self.sum_before(i - 1) - self.sum_before(i - (i & i.wrapping_neg()));
+        self.0.push(sum);
+    }
+
+    fn add(&mut self, ix: usize, n: usize) {
+        let mut i = ix + 1;
+        while i <= self.0.len() {
+            self.0[i - 1] += n;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn sub(&mut self, ix: usize, n: usize) {
+        let mut i = ix + 1;
+        while i <= self.0.len() {
+            self.0[i - 1] -= n;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// The sum of the counts at indices below `ix`.
+    fn sum_before(&self, ix: usize) -> usize {
+        let mut i = ix;
+        let mut sum = 0;
+        while i > 0 {
+            sum += self.0[i - 1];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
+
+impl<'a> Iterator for ElementIter<'a> {
+    type Item = ElementItem<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.moved.contains(&self.tree_ix) {
+            // This element was moved earlier in the node, and already
+            // reported, or is passed over, to be moved later.
+            let ix = self.tree_ix;
+            self.tree_ix += self.tree.count_slots(ix)?;
+            return Some(ElementItem::Vacated(ix));
+        }
+        match self.mutation.get(self.mut_ix)? {
+            MutationItem::Move { from, .. } if *from < self.tree_ix => {
+                // Passed over; the items that follow apply to it in place.
+                let mut here = *self;
+                here.mut_ix += 1;
+                here.tree_ix = *from;
+                let item = here.in_place()?;
+                self.mut_ix = here.mut_ix;
+                self.consumed = here.consumed;
+                Some(ElementItem::Recalled(*from, Box::new(item)))
+            }
+            MutationItem::Move { from, .. } => {
+                let mut child_iter = *self;
+                child_iter.mut_ix += 1;
+                child_iter.tree_ix = *from;
+                let body = child_iter.update_body();
+                child_iter.advance(1);
+                let cur_slots = self.tree.count_slots(*from)?;
+                // The slots of the moved element are covered by the items
+                // following the move, but the position in the tree stays.
+                self.mut_ix += 1;
+                self.consume(cur_slots);
+                let old_body = self.tree.body(*from);
+                Some(ElementItem::Move(*from, old_body, body, child_iter))
+            }
+            _ => self.in_place(),
+        }
+    }
+}
+
+impl<'a> ElementIter<'a> {
+    /// The item for the element at the current location, or an inserted
+    /// element, when the next mutation item is not a move.
+    fn in_place(&mut self) -> Option<ElementItem<'a>> {
         if let Some(cur) = self.mutation.get(self.mut_ix) {
             match cur {
                 MutationItem::Skip(n) => {
-                    let cur_slots = self.tree.count_slots(self.tree_ix)?;
+                    let ix = self.tree_ix;
+                    let cur_slots = self.tree.count_slots(ix)?;
                     if cur_slots + self.consumed <= *n {
                         // Skip this element.
                         self.advance(cur_slots);
-                        // TODO: get fancier about aggregating.
-                        Some(MutIterItem::Skip(1))
+                        Some(ElementItem::Skip(ix))
                     } else {
                         // Element contains a mutation; descend.
-                        let mut child_iter = self.clone();
+                        let mut child_iter = *self;
                         child_iter.advance(1);
                         self.advance(cur_slots);
-                        Some(MutIterItem::Update(None, child_iter))
+                        Some(ElementItem::Update(self.tree.body(ix), None, child_iter))
                     }
                 }
                 MutationItem::Delete(_) => {
-                    let cur_slots = self.tree.count_slots(self.tree_ix)?;
+                    let ix = self.tree_ix;
+                    let cur_slots = self.tree.count_slots(ix)?;
                     self.advance(cur_slots);
-                    Some(MutIterItem::Delete(1))
+                    Some(ElementItem::Delete(ix))
                 }
                 MutationItem::Insert(slots) => {
                     if let Slot::Begin(item) = &slots[self.consumed] {
-                        let mut child_iter = self.clone();
+                        let mut child_iter = *self;
                         child_iter.consumed += 1;
                         let cur_slots = count_slots(&slots[self.consumed..]);
                         self.consumed += cur_slots;
@@ -559,47 +1646,42 @@ impl<'a> Iterator for MutationIter<'a> {
                             self.mut_ix += 1;
                             self.consumed = 0;
                         }
-                        Some(MutIterItem::Insert(item.id, &item.body, child_iter))
+                        Some(ElementItem::Insert(item, child_iter))
                     } else {
                         None
                     }
                 }
                 MutationItem::Update(slots) => {
                     if let Slot::Begin(item) = &slots[self.consumed] {
-                        let mut child_iter = self.clone();
+                        let ix = self.tree_ix;
+                        let mut child_iter = *self;
                         child_iter.advance(1);
-                        let cur_slots = self.tree.count_slots(self.tree_ix)?;
+                        let cur_slots = self.tree.count_slots(ix)?;
                         self.advance(cur_slots);
-                        Some(MutIterItem::Update(Some(&item.body), child_iter))
+                        let old_body = self.tree.body(ix);
+                        Some(ElementItem::Update(old_body, Some(&item.body), child_iter))
                     } else {
                         None
                     }
                 }
+                // A move is not followed by another.
+                MutationItem::Move { .. } => None,
             }
         } else {
             None
         }
     }
-}
-
-impl<'a> MutationIter<'a> {
-    /// Start an iteration over a mutation.
-    pub fn new(tree: &'a Tree, mutation: &'a Mutation) -> MutationIter<'a> {
-        MutationIter {
-            tree,
-            mutation: &mutation.0,
-            tree_ix: 0,
-            mut_ix: 0,
-            consumed: 0,
-        }
-    }
 
     /// Advance the iterator forward by `adv` slots.
     ///
     /// The number of slots is measured relative to the original tree.
-    fn advance(&mut self, mut adv: usize) {
-        //println!("advance {}, tree_ix={}, mut_ix={}, consumed={}", adv, self.tree_ix, self.mut_ix, self.consumed);
+    fn advance(&mut self, adv: usize) {
         self.tree_ix += adv;
+        self.consume(adv);
+    }
+
+    /// Consume `adv` slots of the mutation, without moving in the tree.
+    fn consume(&mut self, mut adv: usize) {
         while adv > 0 {
             let cur = &self.mutation[self.mut_ix];
             match cur {
@@ -613,7 +1695,7 @@ impl<'a> MutationIter<'a> {
                         break;
                     }
                 }
-                MutationItem::Insert(_) => self.mut_ix += 1,
+                MutationItem::Insert(_) | MutationItem::Move { .. } => self.mut_ix += 1,
                 MutationItem::Update(slots) => {
                     let n = slots.len();
                     if n - self.consumed <= adv {
@@ -628,6 +1710,26 @@ impl<'a> MutationIter<'a> {
             }
         }
     }
+
+    /// The new payload of the element at the current location, if updated.
+    fn update_body(&self) -> Option<&'a Payload> {
+        if let Some(MutationItem::Update(slots)) = self.mutation.get(self.mut_ix) {
+            if let Slot::Begin(item) = &slots[self.consumed] {
+                return Some(&item.body);
+            }
+        }
+        None
+    }
+}
+
+impl Payload {
+    /// Whether this payload is backed by a widget.
+    ///
+    /// Other elements are transparent; their children are widgets of the
+    /// nearest enclosing view.
+    pub fn is_widget(&self) -> bool {
+        matches!(self, Payload::View(_))
+    }
 }
 
 // Note: we don't want PartialEq, we want equality in the sense of
@@ -637,7 +1739,11 @@ impl PartialEq for Payload {
         match (self, other) {
             (Payload::View(v1), Payload::View(v2)) => v1.same(v2.as_ref()),
             (Payload::State(s1), Payload::State(s2)) => s1.eq(s2.as_ref()),
+            (Payload::Fragment, Payload::Fragment) => true,
             _ => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,708 @@
+//! Tests of tree diffing.
+//!
+//! Each random case runs a sequence of randomly generated trees through
+//! `MutCursor`, using each of its operations, then checks that applying the mutation yields the new
+//! tree, that walking `MutationIter` over a mirror of the widget tree
+//! yields the same widgets, and that keyed elements keep their ids.
+//! Keyed elements are sometimes reordered with their expected order
+//! given, which is checked to move as few of them as possible. Hand-built
+//! mutations check that `validate` reports each invariant, and that skips
+//! and updates are applied in place.
+
+use std::collections::HashMap;
+use std::panic::Location;
+
+use super::*;
+use crate::view::Label;
+
+/// A xorshift generator, so failures reproduce from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// The kind of payload of an element.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    View,
+    Placeholder,
+    Fragment,
+    State,
+}
+
+impl Kind {
+    fn random(rng: &mut Rng) -> Kind {
+        match rng.below(6) {
+            0 => Kind::Placeholder,
+            1 => Kind::Fragment,
+            2 => Kind::State,
+            _ => Kind::View,
+        }
+    }
+
+    /// Tie the kind to the key, so a keyed element keeps its kind.
+    fn for_key(key: u32) -> Kind {
+        match key % 6 {
+            0 => Kind::Placeholder,
+            1 => Kind::Fragment,
+            2 => Kind::State,
+            _ => Kind::View,
+        }
+    }
+}
+
+/// An element to emit.
+#[derive(Clone, Debug)]
+struct Elem {
+    key: Option<u32>,
+    label: String,
+    kind: Kind,
+}
+
+impl Elem {
+    fn payload(&self) -> Payload {
+        match self.kind {
+            Kind::View => Payload::View(Box::new(Label::new(self.label.clone()))),
+            Kind::Placeholder => Payload::Placeholder,
+            Kind::Fragment => Payload::Fragment,
+            Kind::State => Payload::State(Box::new(self.label.clone())),
+        }
+    }
+}
+
+/// A step in emitting the children of a node.
+#[derive(Debug)]
+enum Op {
+    /// Begin the element by key, as app logic does.
+    Begin(Elem, Vec<Op>),
+    /// Begin the element with `begin_insert`.
+    Insert(Elem, Vec<Op>),
+    /// Update the element at the cursor with `begin_update`.
+    Update(Elem, Vec<Op>),
+    SkipOne,
+    DeleteOne,
+    SkipRest,
+    /// Expect the keyed elements to be begun in this order.
+    Expect(Vec<u32>),
+}
+
+/// An element of the expected tree.
+#[derive(Clone, Debug)]
+struct Spec {
+    elem: Elem,
+    /// Made by `begin_insert`, so it has no key and is never found again.
+    inserted: bool,
+    children: Vec<Spec>,
+}
+
+/// A node of the mirror of the widget tree.
+#[derive(Debug, PartialEq)]
+struct Mirror {
+    id: Id,
+    body: String,
+    children: Vec<Mirror>,
+}
+
+/// Generate the steps emitting the children of a node, and the children
+/// they result in.
+///
+/// `old` is the children of the node in the old tree, if the node is
+/// found there; they are then sometimes visited in place.
+fn gen(rng: &mut Rng, depth: usize, old: Option<&[Spec]>) -> (Vec<Op>, Vec<Spec>) {
+    match old {
+        Some(old) if !old.is_empty() && rng.below(3) == 0 => gen_in_place(rng, depth, old),
+        _ => gen_fresh(rng, depth, old.unwrap_or(&[])),
+    }
+}
+
+fn gen_children(rng: &mut Rng, depth: usize, old: Option<&[Spec]>) -> (Vec<Op>, Vec<Spec>) {
+    if depth > 0 {
+        gen(rng, depth - 1, old)
+    } else {
+        (Vec::new(), Vec::new())
+    }
+}
+
+/// Generate new children, beginning each by key.
+fn gen_fresh(rng: &mut Rng, depth: usize, old: &[Spec]) -> (Vec<Op>, Vec<Spec>) {
+    let n = rng.below(5) as usize;
+    let mut keys: Vec<u32> = (0..8).collect();
+    let mut ops = Vec::new();
+    let mut specs = Vec::new();
+    for _ in 0..n {
+        let key = if rng.below(3) > 0 {
+            let i = rng.below(keys.len() as u64) as usize;
+            Some(keys.remove(i))
+        } else {
+            None
+        };
+        let kind = match key {
+            Some(k) => Kind::for_key(k),
+            None => Kind::random(rng),
+        };
+        let elem = Elem {
+            key,
+            label: format!("l{}", rng.below(3)),
+            kind,
+        };
+        // A keyed element is found wherever it was among the old children.
+        let old_children = key
+            .and_then(|k| old.iter().find(|spec| spec.elem.key == Some(k)))
+            .map(|spec| &spec.children[..]);
+        let (child_ops, children) = gen_children(rng, depth, old_children);
+        ops.push(Op::Begin(elem.clone(), child_ops));
+        specs.push(Spec {
+            elem,
+            inserted: false,
+            children,
+        });
+    }
+    // Expect the keys in the order they are begun, or, to check that a
+    // wrong expectation is harmless, in another order and with one left
+    // out or not begun.
+    let mut expect: Vec<u32> = specs.iter().filter_map(|spec| spec.elem.key).collect();
+    match rng.below(4) {
+        0 => return (ops, specs),
+        1 => {
+            expect.reverse();
+            if rng.below(2) == 0 {
+                expect.pop();
+            } else {
+                expect.push(keys[0]);
+            }
+        }
+        _ => (),
+    }
+    ops.insert(0, Op::Expect(expect));
+    (ops, specs)
+}
+
+/// Generate a new element, inserted with `begin_insert`.
+fn gen_insert(rng: &mut Rng, depth: usize) -> (Op, Spec) {
+    let elem = Elem {
+        key: None,
+        label: format!("l{}", rng.below(3)),
+        kind: Kind::random(rng),
+    };
+    let (child_ops, children) = gen_children(rng, depth, None);
+    let spec = Spec {
+        elem: elem.clone(),
+        inserted: true,
+        children,
+    };
+    (Op::Insert(elem, child_ops), spec)
+}
+
+/// Generate steps visiting the old children in order, each with one of
+/// the cursor's operations, sometimes inserting elements between them or
+/// ending early with `skip_rest`.
+fn gen_in_place(rng: &mut Rng, depth: usize, old: &[Spec]) -> (Vec<Op>, Vec<Spec>) {
+    let mut ops = Vec::new();
+    let mut specs = Vec::new();
+    for (i, spec) in old.iter().enumerate() {
+        if rng.below(5) == 0 {
+            let (op, spec) = gen_insert(rng, depth);
+            ops.push(op);
+            specs.push(spec);
+        }
+        if rng.below(6) == 0 {
+            ops.push(Op::SkipRest);
+            specs.extend_from_slice(&old[i..]);
+            return (ops, specs);
+        }
+        // Unkeyed elements are found by their sequence index, so they are
+        // all begun again to keep the indices in step. Inserted elements
+        // have no key, so they can only be visited at the cursor.
+        let choice = if spec.inserted {
+            1 + rng.below(3)
+        } else if spec.elem.key.is_none() {
+            0
+        } else {
+            rng.below(4)
+        };
+        let mut elem = spec.elem.clone();
+        elem.label = format!("l{}", rng.below(3));
+        match choice {
+            0 => {
+                let (child_ops, children) = gen_children(rng, depth, Some(&spec.children));
+                ops.push(Op::Begin(elem.clone(), child_ops));
+                specs.push(Spec {
+                    elem,
+                    inserted: false,
+                    children,
+                });
+            }
+            1 => {
+                ops.push(Op::SkipOne);
+                specs.push(spec.clone());
+            }
+            2 => ops.push(Op::DeleteOne),
+            _ => {
+                let (child_ops, children) = gen_children(rng, depth, Some(&spec.children));
+                ops.push(Op::Update(elem.clone(), child_ops));
+                specs.push(Spec {
+                    elem,
+                    inserted: spec.inserted,
+                    children,
+                });
+            }
+        }
+    }
+    if rng.below(3) == 0 {
+        let (op, spec) = gen_insert(rng, depth);
+        ops.push(op);
+        specs.push(spec);
+    }
+    (ops, specs)
+}
+
+#[track_caller]
+fn caller() -> &'static Location<'static> {
+    Location::caller()
+}
+
+/// Emit the steps, with unkeyed views and other elements at different
+/// call sites.
+fn emit(
+    cursor: &mut MutCursor,
+    ops: &[Op],
+    placeholder_loc: &'static Location<'static>,
+    view_loc: &'static Location<'static>,
+) {
+    for op in ops {
+        let children = match op {
+            Op::Begin(elem, children) => {
+                let key = match elem.key {
+                    Some(k) => cursor.key_from_loc_user(placeholder_loc, UserKey::new(k)),
+                    None if elem.kind != Kind::View => cursor.key_from_loc(placeholder_loc),
+                    None => cursor.key_from_loc(view_loc),
+                };
+                cursor.begin_internal(key, elem.payload());
+                children
+            }
+            Op::Insert(elem, children) => {
+                cursor.begin_insert(elem.payload());
+                children
+            }
+            Op::Update(elem, children) => {
+                cursor.begin_update(elem.payload());
+                children
+            }
+            Op::SkipOne => {
+                cursor.skip_one();
+                continue;
+            }
+            Op::DeleteOne => {
+                cursor.delete_one();
+                continue;
+            }
+            Op::SkipRest => {
+                cursor.skip_rest();
+                continue;
+            }
+            Op::Expect(keys) => {
+                let keys: Vec<Key> = keys
+                    .iter()
+                    .map(|&k| Key::with_user_key(placeholder_loc, 0, UserKey::new(k)))
+                    .collect();
+                cursor.expect_keys(&keys);
+                continue;
+            }
+        };
+        emit(cursor, children, placeholder_loc, view_loc);
+        cursor.end();
+    }
+}
+
+/// Apply the mutation to the children of a mirror node, the way a
+/// container widget would.
+fn apply(children: &mut Vec<Mirror>, iter: MutationIter) {
+    let mut ix = 0;
+    for item in iter {
+        match item {
+            MutIterItem::Skip(n) => ix += n,
+            MutIterItem::Delete(n) => {
+                children.drain(ix..ix + n);
+            }
+            MutIterItem::Insert(id, body, iter) => {
+                let mut node = Mirror {
+                    id,
+                    body: format!("{:?}", body),
+                    children: Vec::new(),
+                };
+                apply(&mut node.children, iter);
+                children.insert(ix, node);
+                ix += 1;
+            }
+            MutIterItem::Update(body, iter) => {
+                if let Some(body) = body {
+                    children[ix].body = format!("{:?}", body);
+                }
+                apply(&mut children[ix].children, iter);
+                ix += 1;
+            }
+            MutIterItem::Move(n, body, iter) => {
+                let node = children.remove(ix + n);
+                children.insert(ix, node);
+                if let Some(body) = body {
+                    children[ix].body = format!("{:?}", body);
+                }
+                apply(&mut children[ix].children, iter);
+                ix += 1;
+            }
+            MutIterItem::MoveBack(n) => {
+                let node = children.remove(ix - 1 - n);
+                ix -= 1;
+                children.insert(ix, node);
+            }
+        }
+    }
+}
+
+/// Read the tree into mirror nodes, optionally flattening elements that
+/// are not views.
+fn to_mirror(tree: &Tree, flatten: bool) -> Vec<Mirror> {
+    let mut stack = vec![Vec::new()];
+    let mut open = Vec::new();
+    for slot in &tree.slots {
+        match slot {
+            Slot::Begin(item) => {
+                open.push((item.id, &item.body));
+                stack.push(Vec::new());
+            }
+            Slot::End => {
+                let children = stack.pop().unwrap();
+                let (id, body) = open.pop().unwrap();
+                let parent = stack.last_mut().unwrap();
+                if flatten && !body.is_widget() {
+                    parent.extend(children);
+                } else {
+                    parent.push(Mirror {
+                        id,
+                        body: format!("{:?}", body),
+                        children,
+                    });
+                }
+            }
+        }
+    }
+    stack.pop().unwrap()
+}
+
+fn check_spec(nodes: &[Mirror], specs: &[Spec]) {
+    assert_eq!(nodes.len(), specs.len());
+    for (node, spec) in nodes.iter().zip(specs) {
+        match spec.elem.kind {
+            Kind::View => assert_eq!(node.body, format!("View(Label({:?}))", spec.elem.label)),
+            Kind::Placeholder => assert_eq!(node.body, "Placeholder"),
+            Kind::Fragment => assert_eq!(node.body, "Fragment"),
+            Kind::State => assert!(node.body.starts_with("State("), "{}", node.body),
+        }
+        check_spec(&node.children, &spec.children);
+    }
+}
+
+/// The ids of elements reachable by a path of keys.
+fn keyed_ids(nodes: &[Mirror], specs: &[Spec], path: &[u32], ids: &mut HashMap<Vec<u32>, Id>) {
+    for (node, spec) in nodes.iter().zip(specs) {
+        if let Some(key) = spec.elem.key {
+            let mut path = path.to_vec();
+            path.push(key);
+            ids.insert(path.clone(), node.id);
+            keyed_ids(&node.children, &spec.children, &path, ids);
+        }
+    }
+}
+
+#[test]
+fn random_mutations() {
+    let placeholder_loc = caller();
+    let view_loc = caller();
+    for seed in 1..2000u64 {
+        let mut rng = Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let mut tree = Tree::default();
+        let mut mirror = Vec::new();
+        let mut old_specs = Vec::new();
+        for _ in 0..4 {
+            let (ops, specs) = gen(&mut rng, 3, Some(&old_specs));
+            let mut cursor = MutCursor::new(&tree);
+            emit(&mut cursor, &ops, placeholder_loc, view_loc);
+            let mutation = cursor.into_mutation();
+            mutation.validate(&tree).unwrap();
+            apply(&mut mirror, MutationIter::new(&tree, &mutation));
+
+            let mut old_ids = HashMap::new();
+            keyed_ids(&to_mirror(&tree, false), &old_specs, &[], &mut old_ids);
+            tree.mutate(mutation);
+            let nodes = to_mirror(&tree, false);
+            check_spec(&nodes, &specs);
+            assert_eq!(to_mirror(&tree, true), mirror, "seed {}", seed);
+
+            let mut new_ids = HashMap::new();
+            keyed_ids(&nodes, &specs, &[], &mut new_ids);
+            for (path, id) in &new_ids {
+                if let Some(old_id) = old_ids.get(path) {
+                    assert_eq!(old_id, id, "seed {}, key path {:?}", seed, path);
+                }
+            }
+            old_specs = specs;
+        }
+    }
+}
+
+/// Emit a keyed row for each key, each a placeholder containing a view,
+/// with the order of the keys expected.
+fn emit_rows(tree: &Tree, keys: &[u32], loc: &'static Location<'static>) -> Mutation {
+    let mut cursor = MutCursor::new(tree);
+    let row_keys: Vec<Key> = keys
+        .iter()
+        .map(|&k| cursor.key_from_loc_user(loc, UserKey::new(k)))
+        .collect();
+    cursor.expect_keys(&row_keys);
+    for (key, k) in row_keys.into_iter().zip(keys) {
+        cursor.begin_internal(key, Payload::Placeholder);
+        cursor.leaf(Payload::View(Box::new(Label::new(format!("row {}", k)))));
+        cursor.end();
+    }
+    cursor.into_mutation()
+}
+
+#[test]
+fn reordering_moves_only_rows_out_of_order() {
+    let loc = caller();
+    let mut order: Vec<u32> = (0..10).collect();
+    let mut tree = Tree::default();
+    let mut mirror = Vec::new();
+    let mutation = emit_rows(&tree, &order, loc);
+    apply(&mut mirror, MutationIter::new(&tree, &mutation));
+    tree.mutate(mutation);
+    let ids = |nodes: &[Mirror]| nodes.iter().map(|node| node.id).collect::<Vec<_>>();
+
+    // Rotate left, rotate right, then reverse.
+    let steps: [(fn(&mut [u32]), fn(&mut [Id]), usize); 3] = [
+        (|v| v.rotate_left(1), |v| v.rotate_left(1), 1),
+        (|v| v.rotate_right(1), |v| v.rotate_right(1), 1),
+        (|v| v.reverse(), |v| v.reverse(), 9),
+    ];
+    for (i, (reorder, reorder_ids, n_moves)) in steps.iter().enumerate() {
+        reorder(&mut order);
+        let mut expected = ids(&mirror);
+        reorder_ids(&mut expected);
+        let mutation = emit_rows(&tree, &order, loc);
+        let moves = mutation
+            .items
+            .iter()
+            .filter(|item| matches!(item, MutationItem::Move { .. }))
+            .count();
+        assert_eq!(moves, *n_moves, "step {}", i);
+        let widget_moves = MutationIter::new(&tree, &mutation)
+            .filter(|item| matches!(item, MutIterItem::Move(..) | MutIterItem::MoveBack(_)))
+            .count();
+        assert_eq!(widget_moves, *n_moves, "step {}", i);
+        apply(&mut mirror, MutationIter::new(&tree, &mutation));
+        tree.mutate(mutation);
+        assert_eq!(to_mirror(&tree, true), mirror, "step {}", i);
+        // The row widgets survive, in the new order.
+        assert_eq!(ids(&mirror), expected, "step {}", i);
+    }
+}
+
+/// A view `a` containing a view, followed by views `c` and `d`:
+///
+/// ```text
+/// 0 a { 1 b {} 2 } 3, 4 c {} 5, 6 d {} 7
+/// ```
+fn validation_tree() -> Tree {
+    let view = |label: &str, children| {
+        let elem = Elem {
+            key: None,
+            label: label.into(),
+            kind: Kind::View,
+        };
+        Op::Begin(elem, children)
+    };
+    let ops = vec![
+        view("a", vec![view("b", Vec::new())]),
+        view("c", Vec::new()),
+        view("d", Vec::new()),
+    ];
+    let mut tree = Tree::default();
+    let mut cursor = MutCursor::new(&tree);
+    emit(&mut cursor, &ops, caller(), caller());
+    tree.mutate(cursor.into_mutation());
+    assert_eq!(tree.slots.len(), 8);
+    tree
+}
+
+fn new_begin(view: bool) -> Slot {
+    let body = if view {
+        Payload::View(Box::new(Label::new("new")))
+    } else {
+        Payload::Placeholder
+    };
+    Slot::Begin(Item {
+        key: Key::null(),
+        id: Id::new(),
+        body,
+    })
+}
+
+/// A hand-built mutation.
+fn mutation_of(items: Vec<MutationItem>) -> Mutation {
+    let mut mutation = Mutation::new();
+    for item in items {
+        match item {
+            MutationItem::Move { from, n } => mutation.move_element(from, n),
+            item => mutation.items.push(item),
+        }
+    }
+    mutation
+}
+
+/// Validate a hand-built mutation, returning the broken invariant, the
+/// item and the slot.
+fn validate_items(
+    tree: &Tree,
+    items: Vec<MutationItem>,
+) -> Result<(), (Invariant, usize, Option<usize>)> {
+    mutation_of(items)
+        .validate(tree)
+        .map_err(|e| (e.invariant, e.item, e.slot))
+}
+
+#[test]
+fn validate_reports_each_invariant() {
+    use MutationItem::*;
+    let tree = validation_tree();
+    let check = |items| validate_items(&tree, items);
+
+    assert_eq!(check(vec![Skip(8)]), Ok(()));
+    assert_eq!(
+        check(vec![Move { from: 4, n: 1 }, Skip(2), Skip(6)]),
+        Ok(())
+    );
+    // `a` is passed over, then moved back past `c` and `d`, to be kept or
+    // deleted.
+    assert_eq!(
+        check(vec![Skip(4), Move { from: 0, n: 2 }, Skip(4)]),
+        Ok(())
+    );
+    assert_eq!(
+        check(vec![Skip(4), Move { from: 0, n: 2 }, Delete(4)]),
+        Ok(())
+    );
+
+    assert_eq!(
+        check(vec![Skip(9)]),
+        Err((Invariant::TooManySlots, 0, None))
+    );
+    assert_eq!(
+        check(vec![Skip(1)]),
+        Err((Invariant::TooFewSlots, 1, Some(0)))
+    );
+    assert_eq!(
+        check(vec![Delete(1), Skip(7)]),
+        Err((Invariant::PartialDelete, 1, Some(0)))
+    );
+    assert_eq!(
+        check(vec![Insert(vec![new_begin(true)]), Skip(8)]),
+        Err((Invariant::PartialInsert, 0, None))
+    );
+    assert_eq!(
+        check(vec![Update(vec![Slot::End]), Skip(7)]),
+        Err((Invariant::UpdateChangesSlot, 0, Some(0)))
+    );
+    assert_eq!(
+        check(vec![Update(vec![new_begin(false)]), Skip(7)]),
+        Err((Invariant::UpdateChangesView, 0, Some(0)))
+    );
+    // `c` is not a child of `a`.
+    assert_eq!(
+        check(vec![Skip(1), Move { from: 4, n: 0 }, Skip(7)]),
+        Err((Invariant::MoveSource, 1, Some(4)))
+    );
+    // `c` is moved twice.
+    assert_eq!(
+        check(vec![
+            Move { from: 4, n: 1 },
+            Skip(2),
+            Move { from: 4, n: 0 },
+            Skip(6)
+        ]),
+        Err((Invariant::MoveSource, 2, Some(4)))
+    );
+    // `a` and `c` are between the cursor and `d`.
+    assert_eq!(
+        check(vec![Move { from: 6, n: 1 }, Skip(8)]),
+        Err((Invariant::MoveCount, 0, Some(6)))
+    );
+    // `d` is moved ahead of `a`, which is then passed over, so `a` is
+    // moved back past `c` alone.
+    assert_eq!(
+        check(vec![
+            Move { from: 6, n: 2 },
+            Skip(2),
+            Skip(2),
+            Move { from: 0, n: 2 },
+            Skip(4)
+        ]),
+        Err((Invariant::MoveCount, 3, Some(0)))
+    );
+    // Only an element passed over may be deleted after moving it.
+    assert_eq!(
+        check(vec![Move { from: 4, n: 1 }, Delete(2), Skip(6)]),
+        Err((Invariant::MoveNotCovered, 1, Some(4)))
+    );
+}
+
+#[test]
+fn skips_and_updates_apply_in_place() {
+    use MutationItem::*;
+    let mut tree = validation_tree();
+    let counts = (tree.lens.clone(), tree.widgets.clone(), tree.before.clone());
+    let slots = tree.slots.as_ptr();
+
+    // A run that changes nothing skips the whole tree, which leaves it as
+    // it was without rebuilding the slots or the counts.
+    let snapshot = tree.snapshot();
+    tree.mutate(mutation_of(vec![Skip(8)]));
+    assert_eq!(tree.snapshot(), snapshot);
+
+    // Updating the payload of `c` replaces just that slot.
+    let new_c = || Payload::View(Box::new(Label::new("new c")));
+    let (key, id) = match &tree.slots[4] {
+        Slot::Begin(item) => (item.key.clone(), item.id),
+        Slot::End => unreachable!(),
+    };
+    let c = Item {
+        key,
+        id,
+        body: new_c(),
+    };
+    tree.mutate(mutation_of(vec![
+        Skip(4),
+        Update(vec![Slot::Begin(c)]),
+        Skip(3),
+    ]));
+    match &tree.slots[4] {
+        Slot::Begin(item) => assert!(item.id == id && item.body == new_c()),
+        Slot::End => unreachable!(),
+    }
+
+    assert_eq!(tree.slots.as_ptr(), slots);
+    assert_eq!(
+        (tree.lens.clone(), tree.widgets.clone(), tree.before.clone()),
+        counts
+    );
+    assert_eq!(compute_lens(&tree.slots), counts);
+}
@@ -0,0 +1,426 @@
+//! Checking the soundness invariants of a mutation.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::{Mutation, MutationItem, Slot, Sums, Tree};
+use crate::id::Id;
+
+/// A broken invariant of a mutation, found by `Mutation::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MutationError {
+    pub invariant: Invariant,
+    /// The index of the offending item in the mutation.
+    pub item: usize,
+    /// The slot index of the element in the old tree, if it has one.
+    pub slot: Option<usize>,
+    /// The id of the element, if known.
+    pub id: Option<Id>,
+}
+
+/// The soundness invariants of a mutation, as documented on `Mutation`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Invariant {
+    /// The items consume more slots than the old tree has.
+    TooManySlots,
+    /// The items don't consume all slots of the old tree.
+    TooFewSlots,
+    /// A `Delete` covers part of an element.
+    PartialDelete,
+    /// An `Insert` covers part of an element.
+    PartialInsert,
+    /// An `Update` changes a `Begin` into an `End`, or the reverse.
+    UpdateChangesSlot,
+    /// An `Update` changes whether the payload is a view.
+    UpdateChangesView,
+    /// A `Move` is not of an element of the same node not yet visited.
+    MoveSource,
+    /// A `Move` has the wrong count of siblings.
+    MoveCount,
+    /// A `Move` is not followed by items covering its element, or by a
+    /// `Delete` of an element that was not passed over.
+    MoveNotCovered,
+}
+
+/// How an item consumes a slot of the old tree.
+#[derive(Clone, Copy)]
+enum Consume<'a> {
+    Skip,
+    Delete,
+    Update(&'a Slot),
+}
+
+/// The state of validation, following the slots of the old tree in the
+/// order the mutation consumes them.
+struct Validator<'a> {
+    tree: &'a Tree,
+    moved: &'a HashSet<usize>,
+    /// The next slot of the old tree.
+    ix: usize,
+    /// For each moved element being consumed, its end and where to resume.
+    returns: Vec<(usize, usize)>,
+    /// The elements of the old tree being kept, innermost last.
+    open: Vec<usize>,
+    /// For the root and each element in `open`, the slot after the last
+    /// child covered in place, where moves are counted from.
+    frontiers: Vec<usize>,
+    /// The elements of the old tree being deleted, innermost last.
+    deleting: Vec<usize>,
+    /// The elements moved so far.
+    moved_so_far: HashSet<usize>,
+    /// Where each element is in the tree, if anything moves.
+    siblings: Siblings,
+    /// The children moved so far of each node, by position.
+    moved_children: HashMap<Option<usize>, Sums>,
+    /// A moved element the next item must begin to cover, and whether it
+    /// was passed over, so it may be deleted.
+    expect: Option<(usize, bool)>,
+    /// The index of the current item.
+    item: usize,
+}
+
+impl Mutation {
+    /// Check the soundness invariants against the tree the mutation
+    /// applies to.
+    ///
+    /// With debug assertions, this runs on each mutation before any widget
+    /// sees it, so a broken mutation is reported by invariant rather than
+    /// as a panic while applying it.
+    pub fn validate(&self, tree: &Tree) -> Result<(), MutationError> {
+        let mut v = Validator {
+            tree,
+            moved: &self.moved,
+            ix: 0,
+            returns: Vec::new(),
+            open: Vec::new(),
+            frontiers: vec![0],
+            deleting: Vec::new(),
+            moved_so_far: HashSet::new(),
+            siblings: Siblings::default(),
+            moved_children: HashMap::new(),
+            expect: None,
+            item: 0,
+        };
+        // Check move sources up front, as consuming slots relies on them.
+        for (item, mut_item) in self.items.iter().enumerate() {
+            if let MutationItem::Move { from, .. } = mut_item {
+                if tree.count_slots(*from).is_none() {
+                    v.item = item;
+                    return Err(v.error(Invariant::MoveSource, None));
+                }
+            }
+        }
+        if !self.moved.is_empty() {
+            v.siblings = Siblings::new(tree);
+        }
+        // The first element may have been moved.
+        v.skip_moved();
+        for (item, mut_item) in self.items.iter().enumerate() {
+            v.item = item;
+            match mut_item {
+                MutationItem::Skip(n) => {
+                    for _ in 0..*n {
+                        v.consume(Consume::Skip)?;
+                    }
+                }
+                MutationItem::Delete(n) => {
+                    for _ in 0..*n {
+                        v.consume(Consume::Delete)?;
+                    }
+                }
+                MutationItem::Update(new) => {
+                    for slot in new {
+                        v.consume(Consume::Update(slot))?;
+                    }
+                }
+                MutationItem::Insert(new) => v.insert(new)?,
+                MutationItem::Move { from, n } => v.move_element(*from, *n)?,
+            }
+        }
+        v.item = self.items.len();
+        if let Some((from, _)) = v.expect {
+            return Err(v.error(Invariant::MoveNotCovered, Some(from)));
+        }
+        if let Some(&begin) = v.deleting.last() {
+            return Err(v.error(Invariant::PartialDelete, Some(begin)));
+        }
+        if v.ix < tree.slots.len() {
+            let slot = v.open.last().copied();
+            return Err(v.error(Invariant::TooFewSlots, slot));
+        }
+        Ok(())
+    }
+
+    /// With debug assertions, panic with the broken invariant if the
+    /// mutation is not valid for the tree.
+    ///
+    /// Drivers call this, through `MutCursor::into_mutation` or directly,
+    /// before building a `MutationIter`.
+    pub(crate) fn assert_valid(&self, tree: &Tree) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.validate(tree) {
+                panic!("invalid mutation: {}", e);
+            }
+        }
+    }
+}
+
+impl<'a> Validator<'a> {
+    fn consume(&mut self, how: Consume) -> Result<(), MutationError> {
+        if self.ix >= self.tree.slots.len() {
+            return Err(self.error(Invariant::TooManySlots, None));
+        }
+        let slot = self.take();
+        let mut in_place = true;
+        if let Some((from, passed_over)) = self.expect.take() {
+            if slot != from || (matches!(how, Consume::Delete) && !passed_over) {
+                return Err(self.error(Invariant::MoveNotCovered, Some(from)));
+            }
+            in_place = false;
+        }
+        let old = &self.tree.slots[slot];
+        if in_place && self.deleting.is_empty() {
+            if let Slot::Begin(_) = old {
+                *self.frontiers.last_mut().unwrap() = slot + self.tree.lens[slot];
+            }
+        }
+        if let Consume::Delete = how {
+            match old {
+                Slot::Begin(_) => self.deleting.push(slot),
+                Slot::End => {
+                    if self.deleting.pop().is_none() {
+                        let begin = self.open.last().copied();
+                        return Err(self.error(Invariant::PartialDelete, begin));
+                    }
+                }
+            }
+            return Ok(());
+        }
+        if let Some(&begin) = self.deleting.last() {
+            return Err(self.error(Invariant::PartialDelete, Some(begin)));
+        }
+        if let Consume::Update(new) = how {
+            match (old, new) {
+                (Slot::Begin(old_item), Slot::Begin(new_item)) => {
+                    if old_item.body.is_widget() != new_item.body.is_widget() {
+                        return Err(self.error(Invariant::UpdateChangesView, Some(slot)));
+                    }
+                }
+                (Slot::End, Slot::End) => (),
+                _ => {
+                    let begin = match old {
+                        Slot::Begin(_) => Some(slot),
+                        Slot::End => self.open.last().copied(),
+                    };
+                    return Err(self.error(Invariant::UpdateChangesSlot, begin));
+                }
+            }
+        }
+        match old {
+            Slot::Begin(_) => {
+                self.open.push(slot);
+                self.frontiers.push(slot + 1);
+            }
+            Slot::End => {
+                self.open.pop();
+                self.frontiers.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, new: &[Slot]) -> Result<(), MutationError> {
+        if let Some((from, _)) = self.expect {
+            return Err(self.error(Invariant::MoveNotCovered, Some(from)));
+        }
+        if let Some(&begin) = self.deleting.last() {
+            return Err(self.error(Invariant::PartialDelete, Some(begin)));
+        }
+        let mut ids = Vec::new();
+        for slot in new {
+            match slot {
+                Slot::Begin(item) => ids.push(item.id),
+                Slot::End => {
+                    if ids.pop().is_none() {
+                        return Err(self.error(Invariant::PartialInsert, None));
+                    }
+                }
+            }
+        }
+        if let Some(&id) = ids.last() {
+            let mut err = self.error(Invariant::PartialInsert, None);
+            err.id = Some(id);
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn move_element(&mut self, from: usize, n: usize) -> Result<(), MutationError> {
+        if let Some((from, _)) = self.expect {
+            return Err(self.error(Invariant::MoveNotCovered, Some(from)));
+        }
+        if let Some(&begin) = self.deleting.last() {
+            return Err(self.error(Invariant::PartialDelete, Some(begin)));
+        }
+        // Moved elements are never consumed at their original location, so
+        // one that was not moved before is not yet visited.
+        let parent = self.open.last().copied();
+        let sibling = self.siblings.parents[from] == parent;
+        if !sibling || !self.moved_so_far.insert(from) {
+            return Err(self.error(Invariant::MoveSource, Some(from)));
+        }
+        if n != self.count_siblings(parent, from) {
+            return Err(self.error(Invariant::MoveCount, Some(from)));
+        }
+        let passed_over = from < self.ix;
+        self.returns.push((from + self.tree.lens[from], self.ix));
+        self.ix = from;
+        self.expect = Some((from, passed_over));
+        Ok(())
+    }
+
+    /// Count the siblings between the cursor and the child `from` of the
+    /// parent not moved before, and record that it is moved.
+    ///
+    /// Elements passed over are skipped by `skip_moved` as soon as they are
+    /// reached, but count as ahead of the cursor until a later sibling is
+    /// covered, so this counts from the frontier rather than from `ix`.
+    fn count_siblings(&mut self, parent: Option<usize>, from: usize) -> usize {
+        let siblings = &self.siblings;
+        let frontier = *self.frontiers.last().unwrap();
+        let at = match self.tree.slots.get(frontier) {
+            Some(Slot::Begin(_)) if siblings.parents[frontier] == parent => {
+                siblings.positions[frontier]
+            }
+            _ => siblings.counts[&parent],
+        };
+        let pos = siblings.positions[from];
+        let moved = self
+            .moved_children
+            .entry(parent)
+            .or_insert_with(|| Sums::new(siblings.counts[&parent]));
+        let moved_between = |lo: usize, hi: usize| moved.sum_before(hi) - moved.sum_before(lo);
+        let n = if pos >= at {
+            pos - at - moved_between(at, pos)
+        } else {
+            at - pos - 1 - moved_between(pos + 1, at)
+        };
+        moved.add(pos, 1);
+        n
+    }
+
+    /// Take the next slot, as `Tree::mutate` does.
+    fn take(&mut self) -> usize {
+        let slot = self.ix;
+        self.ix += 1;
+        self.skip_moved();
+        slot
+    }
+
+    /// Return from moved elements that are done, and skip those reached
+    /// at their original location.
+    fn skip_moved(&mut self) {
+        loop {
+            if let Some(&(end, resume)) = self.returns.last() {
+                if self.ix == end {
+                    self.returns.pop();
+                    self.ix = resume;
+                    continue;
+                }
+            }
+            if self.moved.contains(&self.ix) {
+                // Already consumed at the location it was moved to.
+                self.ix += self.tree.lens[self.ix];
+                continue;
+            }
+            return;
+        }
+    }
+
+    fn error(&self, invariant: Invariant, slot: Option<usize>) -> MutationError {
+        let id = slot.and_then(|slot| match &self.tree.slots[slot] {
+            Slot::Begin(item) => Some(item.id),
+            Slot::End => None,
+        });
+        MutationError {
+            invariant,
+            item: self.item,
+            slot,
+            id,
+        }
+    }
+}
+
+/// Where each element is in the tree, by slot index.
+#[derive(Default)]
+struct Siblings {
+    /// The slot index of the parent of each element.
+    parents: Vec<Option<usize>>,
+    /// The position of each element among its siblings.
+    positions: Vec<usize>,
+    /// The number of children of each node, and of the root.
+    counts: HashMap<Option<usize>, usize>,
+}
+
+impl Siblings {
+    fn new(tree: &Tree) -> Siblings {
+        let mut parents = vec![None; tree.slots.len()];
+        let mut positions = vec![0; tree.slots.len()];
+        let mut counts = HashMap::new();
+        counts.insert(None, 0);
+        let mut open = Vec::new();
+        for (ix, slot) in tree.slots.iter().enumerate() {
+            match slot {
+                Slot::Begin(_) => {
+                    let parent = open.last().copied();
+                    let count = counts.get_mut(&parent).unwrap();
+                    parents[ix] = parent;
+                    positions[ix] = *count;
+                    *count += 1;
+                    counts.insert(Some(ix), 0);
+                    open.push(ix);
+                }
+                Slot::End => {
+                    open.pop();
+                }
+            }
+        }
+        Siblings {
+            parents,
+            positions,
+            counts,
+        }
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Invariant::TooManySlots => "mutation covers more slots than the tree has",
+            Invariant::TooFewSlots => "mutation doesn't cover all slots of the tree",
+            Invariant::PartialDelete => "delete doesn't cover a whole element",
+            Invariant::PartialInsert => "insert doesn't cover a whole element",
+            Invariant::UpdateChangesSlot => "update changes the slot variant",
+            Invariant::UpdateChangesView => "update changes whether the payload is a view",
+            Invariant::MoveSource => "move is not of an unvisited element in the same node",
+            Invariant::MoveCount => "move has the wrong sibling count",
+            Invariant::MoveNotCovered => "move is not followed by its element",
+        };
+        f.write_str(s)
+    }
+}
+
+impl fmt::Display for MutationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, at mutation item {}", self.invariant, self.item)?;
+        if let Some(slot) = self.slot {
+            write!(f, ", slot {}", slot)?;
+        }
+        if let Some(id) = self.id {
+            write!(f, ", element {:?}", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MutationError {}
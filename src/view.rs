@@ -1,11 +1,12 @@
 //! A description of a widget.
 
 use std::panic::Location;
+use std::time::Duration;
 use std::{any::Any, f64::INFINITY};
 
-use druid::{widget, UnitPoint};
+use druid::{widget, Color, Size, UnitPoint, Vec2};
 
-use crate::any_widget::{Action, AnyWidget, DruidAppData};
+use crate::any_widget::{Action, AnyWidget};
 use crate::cx::Cx;
 use crate::id::Id;
 
@@ -34,6 +35,10 @@ impl Label {
         Label(text.into())
     }
 
+    pub fn text(&self) -> &str {
+        &self.0
+    }
+
     #[track_caller]
     pub fn build(self, cx: &mut Cx) {
         cx.leaf_view(self, Location::caller());
@@ -56,32 +61,128 @@ impl View for Label {
 }
 
 #[derive(Debug)]
-pub struct Button(pub(crate) String);
+pub struct Button {
+    pub(crate) text: String,
+    pub(crate) disabled: bool,
+    pub(crate) style: ButtonStyle,
+}
+
+/// The look of a button.
+///
+/// Each property left unset is taken from the theme.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ButtonStyle {
+    pub text_color: Option<Color>,
+    pub text_size: Option<f64>,
+    /// A flat background, instead of the theme's gradient.
+    pub background: Option<Color>,
+    pub border_color: Option<Color>,
+    pub border_width: Option<f64>,
+    pub border_radius: Option<f64>,
+}
 
 impl Button {
     pub fn new(text: impl Into<String>) -> Button {
-        Button(text.into())
+        Button {
+            text: text.into(),
+            disabled: false,
+            style: ButtonStyle::default(),
+        }
     }
 
+    /// Set whether the button is disabled.
+    ///
+    /// A disabled button is drawn dimmed and doesn't respond to clicks.
+    pub fn disabled(mut self, disabled: bool) -> Button {
+        self.disabled = disabled;
+        self
+    }
+
+    pub fn style(mut self, style: ButtonStyle) -> Button {
+        self.style = style;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    /// Build the button, reporting whether it was clicked.
     #[track_caller]
     pub fn build(self, cx: &mut Cx) -> bool {
+        self.build_count(cx) > 0
+    }
+
+    /// Build the button, reporting the number of clicks since the last run.
+    ///
+    /// Clicks reaching a disabled button, for example from a remote client,
+    /// are dropped.
+    #[track_caller]
+    pub fn build_count(self, cx: &mut Cx) -> usize {
+        let disabled = self.disabled;
         let id = cx.leaf_view(self, Location::caller());
-        cx.app_data.dequeue_action(id).is_some()
+        let clicks = cx
+            .take_events_where(id, |action| matches!(action, Action::Clicked))
+            .len();
+        if disabled {
+            0
+        } else {
+            clicks
+        }
+    }
+}
+
+impl ButtonStyle {
+    pub fn new() -> ButtonStyle {
+        ButtonStyle::default()
+    }
+
+    pub fn text_color(mut self, color: Color) -> ButtonStyle {
+        self.text_color = Some(color);
+        self
+    }
+
+    pub fn text_size(mut self, size: f64) -> ButtonStyle {
+        self.text_size = Some(size);
+        self
+    }
+
+    pub fn background(mut self, color: Color) -> ButtonStyle {
+        self.background = Some(color);
+        self
+    }
+
+    pub fn border_color(mut self, color: Color) -> ButtonStyle {
+        self.border_color = Some(color);
+        self
+    }
+
+    pub fn border_width(mut self, width: f64) -> ButtonStyle {
+        self.border_width = Some(width);
+        self
+    }
+
+    pub fn border_radius(mut self, radius: f64) -> ButtonStyle {
+        self.border_radius = Some(radius);
+        self
     }
 }
 
 impl View for Button {
     fn same(&self, other: &dyn View) -> bool {
         if let Some(other) = other.as_any().downcast_ref::<Self>() {
-            self.0 == other.0
+            self.text == other.text && self.disabled == other.disabled && self.style == other.style
         } else {
             false
         }
     }
 
     fn make_widget(&self, id: Id) -> AnyWidget {
-        let widget = widget::Button::new(self.0.clone())
-            .on_click(move |_, data: &mut DruidAppData, _| data.queue_action(id, Action::Clicked));
+        let widget = crate::widget::Button::new(id, self);
         AnyWidget::MutableWidget(Box::new(widget))
     }
 }
@@ -158,14 +259,25 @@ impl TextBox {
         TextBox(content.into())
     }
 
+    /// Build the text box, reporting the latest edit since the last run.
     #[must_use]
     #[track_caller]
     pub fn build(self, cx: &mut Cx) -> Option<String> {
+        self.build_edits(cx).pop()
+    }
+
+    /// Build the text box, reporting every edit since the last run, in order.
+    #[must_use]
+    #[track_caller]
+    pub fn build_edits(self, cx: &mut Cx) -> Vec<String> {
         let id = cx.leaf_view(self, Location::caller());
-        cx.app_data.dequeue_action(id).map(|action| match action {
-            Action::TextChanged(text) => text,
-            _ => unreachable!("TextBox should never emit any Action other than TextChanged"),
-        })
+        cx.take_actions_where(id, |action| matches!(action, Action::TextChanged(_)))
+            .into_iter()
+            .filter_map(|action| match action {
+                Action::TextChanged(text) => Some(text),
+                _ => None,
+            })
+            .collect()
     }
 }
 
@@ -257,13 +369,7 @@ impl Checkbox {
     pub fn build(self, cx: &mut Cx) -> bool {
         let old_state = self.state;
         let id = cx.leaf_view(self, Location::caller());
-        cx.app_data
-            .dequeue_action(id)
-            .map(|action| match action {
-                Action::Toggled(state) => state,
-                _ => unreachable!("Checkbox should never emit any Action other than Toggled"),
-            })
-            .unwrap_or(old_state)
+        last_toggle(cx, id).unwrap_or(old_state)
     }
 }
 
@@ -282,6 +388,213 @@ impl View for Checkbox {
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub struct Slider {
+    pub(crate) value: f64,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) step: Option<f64>,
+}
+
+impl Slider {
+    /// A slider over the range 0 to 1.
+    pub fn new(value: f64) -> Slider {
+        Slider {
+            value,
+            min: 0.0,
+            max: 1.0,
+            step: None,
+        }
+    }
+
+    pub fn range(mut self, min: f64, max: f64) -> Slider {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    /// Snap values to multiples of the step, counting from the minimum.
+    pub fn step(mut self, step: f64) -> Slider {
+        self.step = Some(step);
+        self
+    }
+
+    /// Build the slider, returning its new value.
+    ///
+    /// The value is snapped wherever it came from, so a value set by a
+    /// remote client or `Headless` also keeps to the range and step.
+    #[must_use]
+    #[track_caller]
+    pub fn build(self, cx: &mut Cx) -> f64 {
+        let (old_value, min, max, step) = (self.value, self.min, self.max, self.step);
+        let id = cx.leaf_view(self, Location::caller());
+        last_value(cx, id)
+            .map(|value| snap(value, min, max, step))
+            .unwrap_or(old_value)
+    }
+}
+
+/// Clamp a value to the range and snap it to multiples of the step,
+/// counting from the minimum, as the slider does.
+pub(crate) fn snap(value: f64, min: f64, max: f64, step: Option<f64>) -> f64 {
+    let value = value.max(min).min(max);
+    match step {
+        Some(step) if step > 0.0 => (min + ((value - min) / step).round() * step).min(max),
+        _ => value,
+    }
+}
+
+impl View for Slider {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, id: Id) -> AnyWidget {
+        let widget = crate::widget::Slider::new(id, self);
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
+
+/// A pair of buttons for stepping a value up and down.
+#[derive(Debug, PartialEq)]
+pub struct Stepper {
+    pub(crate) value: f64,
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) step: f64,
+    pub(crate) wraparound: bool,
+}
+
+impl Stepper {
+    /// An unbounded stepper, with a step of 1.
+    pub fn new(value: f64) -> Stepper {
+        Stepper {
+            value,
+            min: std::f64::MIN,
+            max: std::f64::MAX,
+            step: 1.0,
+            wraparound: false,
+        }
+    }
+
+    pub fn range(mut self, min: f64, max: f64) -> Stepper {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn step(mut self, step: f64) -> Stepper {
+        self.step = step;
+        self
+    }
+
+    /// Set whether stepping past one end of the range wraps to the other.
+    pub fn wraparound(mut self, wraparound: bool) -> Stepper {
+        self.wraparound = wraparound;
+        self
+    }
+
+    /// Build the stepper, returning its new value.
+    ///
+    /// A value out of the range, as a remote client or `Headless` can set,
+    /// is clamped, or wrapped to the other end with `wraparound`.
+    #[must_use]
+    #[track_caller]
+    pub fn build(self, cx: &mut Cx) -> f64 {
+        let (old_value, min, max, wraparound) = (self.value, self.min, self.max, self.wraparound);
+        let id = cx.leaf_view(self, Location::caller());
+        last_value(cx, id)
+            .map(|value| {
+                if wraparound && value > max {
+                    min
+                } else if wraparound && value < min {
+                    max
+                } else {
+                    value.max(min).min(max)
+                }
+            })
+            .unwrap_or(old_value)
+    }
+}
+
+impl View for Stepper {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, id: Id) -> AnyWidget {
+        let widget = crate::widget::Stepper::new(id, self);
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
+
+/// Take the value changes of the element, returning the last, for the
+/// numeric views.
+fn last_value(cx: &mut Cx, id: Id) -> Option<f64> {
+    cx.take_actions_where(id, |action| matches!(action, Action::ValueChanged(_)))
+        .into_iter()
+        .filter_map(|action| match action {
+            Action::ValueChanged(value) => Some(value),
+            _ => None,
+        })
+        .last()
+}
+
+/// Take the toggles of the element, returning the last state, for the
+/// checkbox and switch.
+fn last_toggle(cx: &mut Cx, id: Id) -> Option<bool> {
+    cx.take_actions_where(id, |action| matches!(action, Action::Toggled(_)))
+        .into_iter()
+        .filter_map(|action| match action {
+            Action::Toggled(state) => Some(state),
+            _ => None,
+        })
+        .last()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Switch {
+    pub(crate) state: bool,
+}
+
+impl Switch {
+    pub fn new(state: bool) -> Switch {
+        Switch { state }
+    }
+
+    /// Build the switch, returning its new state.
+    #[must_use]
+    #[track_caller]
+    pub fn build(self, cx: &mut Cx) -> bool {
+        let old_state = self.state;
+        let id = cx.leaf_view(self, Location::caller());
+        last_toggle(cx, id).unwrap_or(old_state)
+    }
+}
+
+impl View for Switch {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, id: Id) -> AnyWidget {
+        let widget = crate::widget::Switch::new(id, self.state);
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
+
 /// A wrapper for detecting click gestures.
 #[derive(Debug)]
 pub struct Clicked;
@@ -297,7 +610,8 @@ impl Clicked {
         let id = cx.begin_view(Box::new(self), Location::caller());
         f(cx);
         cx.end();
-        cx.app_data.dequeue_action(id).is_some()
+        !cx.take_events_where(id, |action| matches!(action, Action::Clicked))
+            .is_empty()
     }
 }
 
@@ -364,6 +678,40 @@ impl<D: druid::Data> View for Painter<D> {
     }
 }
 
+/// A stand-in for a view with no data form, made by a renderer from
+/// `ViewData::Opaque`.
+///
+/// It holds the `Debug` output of the original view, such as a `Painter`,
+/// and is drawn as a placeholder showing it. Sent back over the wire, it
+/// is described as the same opaque view again.
+#[derive(Debug, PartialEq)]
+pub struct Opaque(pub(crate) String);
+
+impl Opaque {
+    pub fn new(description: impl Into<String>) -> Opaque {
+        Opaque(description.into())
+    }
+
+    pub fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl View for Opaque {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, _id: Id) -> AnyWidget {
+        let widget = crate::widget::Opaque::new(self);
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 pub struct SizedBox {
     pub(crate) width: Option<f64>,
@@ -536,3 +884,166 @@ impl View for Align {
         AnyWidget::MutableWidget(Box::new(widget))
     }
 }
+
+/// A top-level window.
+///
+/// In a `MultiWindowApp`, each window element at the top level of the app
+/// logic is a window, opened when the element is inserted and closed when
+/// it is deleted. Its title and size follow the view. Elsewhere, such as
+/// in an `AppHolder`, the content is shown in place.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Window {
+    pub(crate) title: String,
+    pub(crate) size: Option<Size>,
+}
+
+impl Window {
+    pub fn new(title: impl Into<String>) -> Window {
+        Window {
+            title: title.into(),
+            size: None,
+        }
+    }
+
+    /// Set the size of the window's content.
+    pub fn size(mut self, size: impl Into<Size>) -> Self {
+        self.size = Some(size.into());
+        self
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Build the window, reporting whether the user closed it.
+    ///
+    /// A window closed by the user is not reopened; the app logic should
+    /// stop emitting it.
+    #[track_caller]
+    pub fn build(self, cx: &mut Cx, f: impl FnOnce(&mut Cx)) -> bool {
+        let id = cx.begin_view(Box::new(self), Location::caller());
+        f(cx);
+        cx.end();
+        !cx.take_events_where(id, |action| matches!(action, Action::WindowClosed))
+            .is_empty()
+    }
+}
+
+impl View for Window {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, _id: Id) -> AnyWidget {
+        let widget = crate::widget::SizedBox::new(&Default::default());
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
+
+/// A scroll container for its content.
+///
+/// The offset can be set by the app logic, either directly or by naming a
+/// view to bring into view. It is applied when it changes, so the user is
+/// free to scroll in between.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scroll {
+    pub(crate) vertical: bool,
+    pub(crate) horizontal: bool,
+    pub(crate) target: Option<ScrollTarget>,
+    pub(crate) animation: Option<Duration>,
+}
+
+/// Where a scroll view is asked to scroll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ScrollTarget {
+    Offset(Vec2),
+    /// Bring the view with the id into view.
+    Node(Id),
+}
+
+impl Scroll {
+    /// A scroll container scrolling in both directions.
+    pub fn new() -> Scroll {
+        Scroll {
+            vertical: true,
+            horizontal: true,
+            target: None,
+            animation: None,
+        }
+    }
+
+    /// Only scroll vertically, fitting the content to the width.
+    pub fn vertical(mut self) -> Self {
+        self.vertical = true;
+        self.horizontal = false;
+        self
+    }
+
+    /// Only scroll horizontally, fitting the content to the height.
+    pub fn horizontal(mut self) -> Self {
+        self.vertical = false;
+        self.horizontal = true;
+        self
+    }
+
+    /// Scroll to the offset of the content.
+    pub fn offset(mut self, offset: impl Into<Vec2>) -> Self {
+        self.target = Some(ScrollTarget::Offset(offset.into()));
+        self
+    }
+
+    /// Scroll just enough to show the view with the id.
+    ///
+    /// The view is shown once when the id is set; to show it again after
+    /// the user scrolled away, clear it for a run.
+    pub fn scroll_to(mut self, id: Id) -> Self {
+        self.target = Some(ScrollTarget::Node(id));
+        self
+    }
+
+    /// Animate scrolling by the app logic over the duration.
+    pub fn animate(mut self, duration: Duration) -> Self {
+        self.animation = Some(duration);
+        self
+    }
+
+    /// Build the scroll container, reporting the new offset if it changed.
+    ///
+    /// Scrolling by the user is reported as it happens, scrolling by the
+    /// app logic when it is complete. Changes to the viewport size are
+    /// ignored.
+    #[track_caller]
+    pub fn build(self, cx: &mut Cx, f: impl FnOnce(&mut Cx)) -> Option<Vec2> {
+        let id = cx.begin_view(Box::new(self), Location::caller());
+        f(cx);
+        cx.end();
+        cx.take_events_where(id, |action| {
+            matches!(action, Action::Scrolled(_) | Action::Resized(_))
+        })
+        .into_iter()
+        .filter_map(|action| match action {
+            Action::Scrolled(offset) => Some(offset),
+            _ => None,
+        })
+        .last()
+    }
+}
+
+impl View for Scroll {
+    fn same(&self, other: &dyn View) -> bool {
+        if let Some(other) = other.as_any().downcast_ref::<Self>() {
+            self == other
+        } else {
+            false
+        }
+    }
+
+    fn make_widget(&self, id: Id) -> AnyWidget {
+        let widget = crate::widget::Scroll::new(id, self);
+        AnyWidget::MutableWidget(Box::new(widget))
+    }
+}
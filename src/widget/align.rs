@@ -1,6 +1,6 @@
 //! A widget that aligns its child (for example, centering it).
 
-use crate::{view, DruidAppData, MutableWidget, Payload, SingleChild};
+use crate::{view, DruidAppData, Id, MutableWidget, Payload, SingleChild};
 use druid::widget::prelude::*;
 use druid::{Rect, Size};
 
@@ -35,6 +35,10 @@ impl MutableWidget for Align {
 
         self.child.mutate(ctx, mut_iter);
     }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        self.child.locate(id)
+    }
 }
 
 impl Widget<DruidAppData> for Align {
@@ -0,0 +1,214 @@
+use druid::widget::Label;
+use druid::{
+    theme, widget::prelude::*, Affine, Color, Insets, LinearGradient, MouseButton, UnitPoint,
+};
+
+use crate::{
+    any_widget::Action, view, view::ButtonStyle, DruidAppData, Id, MutableWidget, MutationIter,
+    Payload,
+};
+
+// The minimum padding added to a button, as in Druid.
+const LABEL_INSETS: Insets = Insets::uniform_xy(8., 2.);
+
+/// The opacity of a disabled button.
+const DISABLED_ALPHA: f64 = 0.4;
+
+/// The text color of a disabled button without a text color of its own.
+const DISABLED_TEXT_COLOR: Color = Color::rgb8(0x80, 0x80, 0x80);
+
+/// A button, adapted from `druid::widget::Button`.
+///
+/// Unlike Druid's, the label and style can be changed by a mutation, and
+/// the button can be disabled.
+pub struct Button {
+    id: Id,
+    label: Label<DruidAppData>,
+    label_size: Size,
+    disabled: bool,
+    style: ButtonStyle,
+}
+
+impl Button {
+    pub fn new(id: Id, view: &view::Button) -> Self {
+        let mut button = Button {
+            id,
+            label: Label::new(view.text.clone()),
+            label_size: Size::ZERO,
+            disabled: view.disabled,
+            style: view.style.clone(),
+        };
+        button.update_label();
+        button
+    }
+
+    /// Apply the style and disabled state to the label.
+    ///
+    /// A layout is needed for the change to show.
+    fn update_label(&mut self) {
+        match (&self.style.text_color, self.disabled) {
+            (Some(color), false) => self.label.set_text_color(color.clone()),
+            (Some(color), true) => self
+                .label
+                .set_text_color(color.clone().with_alpha(DISABLED_ALPHA)),
+            (None, false) => self.label.set_text_color(theme::LABEL_COLOR),
+            (None, true) => self.label.set_text_color(DISABLED_TEXT_COLOR),
+        }
+        match self.style.text_size {
+            Some(size) => self.label.set_text_size(size),
+            None => self.label.set_text_size(theme::TEXT_SIZE_NORMAL),
+        }
+    }
+}
+
+impl MutableWidget for Button {
+    fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, _mut_iter: MutationIter) {
+        if let Some(Payload::View(view)) = body {
+            if let Some(v) = view.as_any().downcast_ref::<view::Button>() {
+                self.label.set_text(v.text.clone());
+                self.disabled = v.disabled;
+                if self.disabled {
+                    ctx.set_active(false);
+                }
+                self.style = v.style.clone();
+                self.update_label();
+                ctx.request_update();
+                ctx.request_layout();
+            }
+        }
+    }
+}
+
+impl Widget<DruidAppData> for Button {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, _env: &Env) {
+        if self.disabled {
+            return;
+        }
+        match event {
+            Event::MouseDown(mouse_event) => {
+                if mouse_event.button == MouseButton::Left {
+                    ctx.set_active(true);
+                    ctx.request_paint();
+                }
+            }
+            Event::MouseUp(mouse_event) => {
+                if ctx.is_active() && mouse_event.button == MouseButton::Left {
+                    ctx.set_active(false);
+                    if ctx.is_hot() {
+                        data.queue_action(self.id, Action::Clicked);
+                    }
+                    ctx.request_paint();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(_) = event {
+            ctx.request_paint();
+        }
+        self.label.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.label.update(ctx, old_data, data, env)
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        bc.debug_check("Button");
+        let padding = Size::new(LABEL_INSETS.x_value(), LABEL_INSETS.y_value());
+        let label_bc = bc.shrink(padding).loosen();
+        self.label_size = self.label.layout(ctx, &label_bc, data, env);
+        // As in Druid, be at least as tall as a default textbox.
+        let min_height = env.get(theme::BORDERED_WIDGET_HEIGHT);
+        let baseline = self.label.baseline_offset();
+        ctx.set_baseline_offset(baseline + LABEL_INSETS.y1);
+
+        bc.constrain(Size::new(
+            self.label_size.width + padding.width,
+            (self.label_size.height + padding.height).max(min_height),
+        ))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        let is_active = ctx.is_active();
+        let is_hot = ctx.is_hot() && !self.disabled;
+        let size = ctx.size();
+        let stroke_width = self
+            .style
+            .border_width
+            .unwrap_or_else(|| env.get(theme::BUTTON_BORDER_WIDTH));
+        let radius = self
+            .style
+            .border_radius
+            .unwrap_or_else(|| env.get(theme::BUTTON_BORDER_RADIUS));
+        let alpha = if self.disabled { DISABLED_ALPHA } else { 1.0 };
+
+        let rounded_rect = size
+            .to_rect()
+            .inset(-stroke_width / 2.0)
+            .to_rounded_rect(radius);
+
+        let border_color = match &self.style.border_color {
+            Some(color) => color.clone(),
+            None if is_hot => env.get(theme::BORDER_LIGHT),
+            None => env.get(theme::BORDER_DARK),
+        };
+        ctx.stroke(rounded_rect, &border_color.with_alpha(alpha), stroke_width);
+
+        match &self.style.background {
+            Some(color) => {
+                let color = if is_active {
+                    darken(color)
+                } else {
+                    color.clone()
+                };
+                ctx.fill(rounded_rect, &color.with_alpha(alpha));
+            }
+            None => {
+                let (top, bottom) = if is_active {
+                    (env.get(theme::BUTTON_DARK), env.get(theme::BUTTON_LIGHT))
+                } else {
+                    (env.get(theme::BUTTON_LIGHT), env.get(theme::BUTTON_DARK))
+                };
+                let gradient = LinearGradient::new(
+                    UnitPoint::TOP,
+                    UnitPoint::BOTTOM,
+                    (top.with_alpha(alpha), bottom.with_alpha(alpha)),
+                );
+                ctx.fill(rounded_rect, &gradient);
+            }
+        }
+
+        let label_offset = (size.to_vec2() - self.label_size.to_vec2()) / 2.0;
+        ctx.with_save(|ctx| {
+            ctx.transform(Affine::translate(label_offset));
+            self.label.paint(ctx, data, env);
+        });
+    }
+}
+
+/// The color of a pressed button with a flat background.
+fn darken(color: &Color) -> Color {
+    let (r, g, b, a) = color.as_rgba();
+    Color::rgba(r * 0.8, g * 0.8, b * 0.8, a)
+}
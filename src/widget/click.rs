@@ -1,4 +1,4 @@
-use druid::{widget::prelude::*, MouseButton, Point};
+use druid::{widget::prelude::*, MouseButton, Point, Rect};
 
 use crate::{
     any_widget::Action, DruidAppData, Id, MutableWidget, MutationIter, Payload, SingleChild,
@@ -22,6 +22,10 @@ impl MutableWidget for Click {
     fn mutate(&mut self, ctx: &mut EventCtx, _body: Option<&Payload>, mut_iter: MutationIter) {
         self.child.mutate(ctx, mut_iter);
     }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        self.child.locate(id)
+    }
 }
 
 impl Widget<DruidAppData> for Click {
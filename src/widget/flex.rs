@@ -30,7 +30,7 @@ use druid::{
 
 use crate::{
     any_widget::{AnyWidget, DruidAppData},
-    MutableWidget, Payload,
+    Id, MutableWidget, Payload,
 };
 use crate::{MutIterItem, MutationIter};
 
@@ -161,6 +161,8 @@ pub struct Flex {
 struct ChildWidget {
     widget: WidgetPod<DruidAppData, AnyWidget>,
     params: FlexParams,
+    /// The id of the child's view, if it was added by a mutation.
+    id: Option<Id>,
 }
 
 /// A dummy widget we use to do spacing.
@@ -285,6 +287,7 @@ impl ChildWidget {
         ChildWidget {
             widget: WidgetPod::new(child),
             params,
+            id: None,
         }
     }
 }
@@ -572,7 +575,8 @@ impl MutableWidget for Flex {
                     let child = AnyWidget::mutate_insert(ctx, id, body, child_iter);
                     // TODO: extract the flex params from the mutation.
                     let params = 0.0;
-                    let child_widget = ChildWidget::new(child, params.into());
+                    let mut child_widget = ChildWidget::new(child, params.into());
+                    child_widget.id = Some(id);
                     self.children.insert(ix, child_widget);
                     ix += 1;
                     children_changed = true;
@@ -585,12 +589,36 @@ impl MutableWidget for Flex {
                         });
                     ix += 1;
                 }
+                MutIterItem::Move(n, body, child_iter) => {
+                    let child_widget = self.children.remove(ix + n);
+                    self.children.insert(ix, child_widget);
+                    self.children[ix]
+                        .widget
+                        .with_event_context(ctx, |child, ctx| {
+                            child.mutate_update(ctx, body, child_iter);
+                        });
+                    ix += 1;
+                    children_changed = true;
+                }
+                MutIterItem::MoveBack(n) => {
+                    let child_widget = self.children.remove(ix - 1 - n);
+                    ix -= 1;
+                    self.children.insert(ix, child_widget);
+                    children_changed = true;
+                }
             }
         }
         if children_changed {
             ctx.children_changed();
         }
     }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        self.children.iter().find_map(|child| {
+            let child_id = child.id?;
+            super::locate_in_child(&child.widget, child_id, id)
+        })
+    }
 }
 
 impl Widget<DruidAppData> for Flex {
@@ -16,20 +16,69 @@ pub use padding::Padding;
 mod checkbox;
 pub use checkbox::Checkbox;
 
+mod button;
+pub use button::Button;
+
 mod click;
 pub use click::Click;
 
+mod slider;
+pub use slider::Slider;
+
+mod stepper;
+pub use stepper::Stepper;
+
+mod switch;
+pub use switch::Switch;
+
+mod scroll;
+pub use scroll::Scroll;
+
 mod sized_box;
 pub use sized_box::SizedBox;
 
 mod painter;
 pub use painter::Painter;
 
+mod opaque;
+pub use opaque::Opaque;
+
 mod align;
 pub use align::Align;
 
-use crate::{DruidAppData, MutationIter, Payload};
-use druid::EventCtx;
+use crate::{any_widget::AnyWidget, DruidAppData, Id, MutationIter, Payload};
+use druid::{EventCtx, Rect, WidgetPod};
 pub trait MutableWidget: druid::Widget<DruidAppData> {
+    /// Apply a mutation to this widget and its children.
+    ///
+    /// `body` is the new payload, if it changed. Containers apply each
+    /// `MutIterItem` of `mut_iter` to their children, including moves.
     fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, mut_iter: MutationIter);
+
+    /// The layout rect of the descendant widget of the view with the id,
+    /// relative to this widget, as of the last layout.
+    ///
+    /// Containers implement this, so a scroll view can bring a node into
+    /// view.
+    fn locate(&self, _id: Id) -> Option<Rect> {
+        None
+    }
+}
+
+/// Locate the view with the id in a child with the id `child_id`, for
+/// implementing `MutableWidget::locate`.
+pub(crate) fn locate_in_child(
+    child: &WidgetPod<DruidAppData, AnyWidget>,
+    child_id: Id,
+    id: Id,
+) -> Option<Rect> {
+    let rect = child.layout_rect();
+    if child_id == id {
+        Some(rect)
+    } else {
+        child
+            .widget()
+            .locate(id)
+            .map(|r| r + rect.origin().to_vec2())
+    }
 }
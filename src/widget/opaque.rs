@@ -0,0 +1,89 @@
+use druid::widget::Label;
+use druid::{theme, widget::prelude::*, Affine, Color, Insets};
+
+use crate::{view, DruidAppData, MutableWidget, MutationIter, Payload};
+
+const LABEL_INSETS: Insets = Insets::uniform_xy(4., 2.);
+
+/// The color of the description and the border.
+const PLACEHOLDER_COLOR: Color = Color::rgb8(0x80, 0x80, 0x80);
+
+/// A stand-in for a view that a renderer can't make.
+///
+/// It shows the description of the view in gray, in a box, so that it
+/// isn't mistaken for a label of the app's own.
+pub struct Opaque {
+    label: Label<DruidAppData>,
+    label_size: Size,
+}
+
+impl Opaque {
+    pub fn new(view: &view::Opaque) -> Self {
+        let mut label = Label::new(view.0.clone());
+        label.set_text_color(PLACEHOLDER_COLOR);
+        Opaque {
+            label,
+            label_size: Size::ZERO,
+        }
+    }
+}
+
+impl MutableWidget for Opaque {
+    fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, _mut_iter: MutationIter) {
+        if let Some(Payload::View(view)) = body {
+            if let Some(v) = view.as_any().downcast_ref::<view::Opaque>() {
+                self.label.set_text(v.0.clone());
+                ctx.request_update();
+                ctx.request_layout();
+            }
+        }
+    }
+}
+
+impl Widget<DruidAppData> for Opaque {
+    fn event(&mut self, _: &mut EventCtx, _: &Event, _: &mut DruidAppData, _: &Env) {}
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.label.lifecycle(ctx, event, data, env)
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.label.update(ctx, old_data, data, env)
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        bc.debug_check("Opaque");
+        let padding = Size::new(LABEL_INSETS.x_value(), LABEL_INSETS.y_value());
+        let label_bc = bc.shrink(padding).loosen();
+        self.label_size = self.label.layout(ctx, &label_bc, data, env);
+        bc.constrain(self.label_size + padding)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        let stroke_width = env.get(theme::BUTTON_BORDER_WIDTH);
+        let rect = ctx.size().to_rect().inset(-stroke_width / 2.0);
+        ctx.stroke(rect, &PLACEHOLDER_COLOR, stroke_width);
+        ctx.with_save(|ctx| {
+            ctx.transform(Affine::translate((LABEL_INSETS.x0, LABEL_INSETS.y0)));
+            self.label.paint(ctx, data, env);
+        });
+    }
+}
@@ -14,7 +14,7 @@
 
 //! A widget that just adds padding during layout.
 
-use crate::{view, DruidAppData, MutableWidget, MutationIter, Payload, SingleChild};
+use crate::{view, DruidAppData, Id, MutableWidget, MutationIter, Payload, SingleChild};
 use druid::kurbo::{Insets, Point, Rect, Size};
 use druid::{
     BoxConstraints, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx, PaintCtx, UpdateCtx,
@@ -47,6 +47,10 @@ impl MutableWidget for Padding {
 
         self.child.mutate(ctx, mut_iter);
     }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        self.child.locate(id)
+    }
 }
 
 impl Padding {
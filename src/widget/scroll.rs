@@ -0,0 +1,335 @@
+use druid::{widget::prelude::*, Point, Rect, Vec2};
+
+use crate::{
+    any_widget::Action, view, view::ScrollTarget, DruidAppData, Id, MutableWidget, MutationIter,
+    Payload, SingleChild,
+};
+
+/// A scroll container, wrapping `druid::widget::Scroll`.
+///
+/// The offset can be set by the view, optionally animated. Offset changes
+/// are reported as actions: those made by the user as they happen, and
+/// those made by the view when they are complete. Changes to the size of
+/// the viewport are reported too, so the app logic can build only what
+/// is visible.
+pub struct Scroll {
+    id: Id,
+    inner: druid::widget::Scroll<DruidAppData, Content>,
+    /// Whether scrolling is vertical and horizontal.
+    axes: (bool, bool),
+    /// The target of the view, applied when it changes.
+    target: Option<ScrollTarget>,
+    animation: Option<f64>,
+    /// A target to be resolved once the content is laid out.
+    pending: Option<ScrollTarget>,
+    laid_out: bool,
+    animating: Option<Animation>,
+    /// The size changed and has not been reported yet.
+    resized: bool,
+}
+
+/// An animated scroll in progress.
+struct Animation {
+    from: Vec2,
+    to: Vec2,
+    /// The elapsed and total time, in seconds.
+    elapsed: f64,
+    duration: f64,
+}
+
+/// The content of the scroll container.
+struct Content {
+    child: SingleChild,
+}
+
+impl Scroll {
+    pub fn new(id: Id, view: &view::Scroll) -> Self {
+        let axes = (view.vertical, view.horizontal);
+        let mut scroll = Scroll {
+            id,
+            inner: make_inner(Content::new(), axes),
+            axes,
+            target: None,
+            animation: None,
+            pending: None,
+            laid_out: false,
+            animating: None,
+            resized: false,
+        };
+        scroll.set_view(view);
+        scroll
+    }
+
+    fn set_view(&mut self, view: &view::Scroll) {
+        self.animation = view.animation.map(|duration| duration.as_secs_f64());
+        if view.target != self.target {
+            self.target = view.target;
+            self.pending = view.target;
+        }
+    }
+
+    /// The offset that shows the target, within the bounds of the content.
+    fn resolve(&self, target: ScrollTarget, viewport: Size) -> Option<Vec2> {
+        let offset = self.inner.offset();
+        let to = match target {
+            ScrollTarget::Offset(to) => to,
+            ScrollTarget::Node(id) => {
+                let rect = self.inner.child().child.locate(id)?;
+                Vec2::new(
+                    show_range(offset.x, viewport.width, rect.x0, rect.x1),
+                    show_range(offset.y, viewport.height, rect.y0, rect.y1),
+                )
+            }
+        };
+        let max = self.inner.child_size() - viewport;
+        let (vertical, horizontal) = self.axes;
+        Some(Vec2::new(
+            if horizontal {
+                to.x.min(max.width).max(0.0)
+            } else {
+                offset.x
+            },
+            if vertical {
+                to.y.min(max.height).max(0.0)
+            } else {
+                offset.y
+            },
+        ))
+    }
+
+    /// Scroll to the offset, reporting whether it changed.
+    fn scroll_to(&mut self, ctx: &mut EventCtx, to: Vec2) -> bool {
+        let changed = self.inner.scroll_by(to - self.inner.offset());
+        if changed {
+            ctx.request_paint();
+        }
+        changed
+    }
+
+    /// Advance scrolling by the view, for an animation frame.
+    fn animate(&mut self, ctx: &mut EventCtx, interval: u64, data: &mut DruidAppData) {
+        if let Some(target) = self.pending {
+            if !self.laid_out {
+                ctx.request_anim_frame();
+                return;
+            }
+            self.pending = None;
+            if let Some(to) = self.resolve(target, ctx.size()) {
+                match self.animation {
+                    Some(duration) if duration > 0.0 => {
+                        self.animating = Some(Animation {
+                            from: self.inner.offset(),
+                            to,
+                            elapsed: 0.0,
+                            duration,
+                        });
+                    }
+                    _ => {
+                        if self.scroll_to(ctx, to) {
+                            self.report(data);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(animation) = &mut self.animating {
+            animation.elapsed += interval as f64 * 1e-9;
+            let t = (animation.elapsed / animation.duration).min(1.0);
+            let eased = t * t * (3.0 - 2.0 * t);
+            let to = animation.from.lerp(animation.to, eased);
+            if t < 1.0 {
+                ctx.request_anim_frame();
+            } else {
+                self.animating = None;
+            }
+            self.scroll_to(ctx, to);
+            if self.animating.is_none() {
+                self.report(data);
+            }
+        }
+    }
+
+    fn report(&self, data: &mut DruidAppData) {
+        data.queue_action(self.id, Action::Scrolled(self.inner.offset()));
+    }
+}
+
+/// The start of a range of length `len` that shows `lo..hi`, moving as
+/// little as possible from `start`.
+fn show_range(start: f64, len: f64, lo: f64, hi: f64) -> f64 {
+    if lo < start || hi - lo > len {
+        lo
+    } else if hi > start + len {
+        hi - len
+    } else {
+        start
+    }
+}
+
+fn make_inner(
+    content: Content,
+    axes: (bool, bool),
+) -> druid::widget::Scroll<DruidAppData, Content> {
+    let scroll = druid::widget::Scroll::new(content);
+    match axes {
+        (true, false) => scroll.vertical(),
+        (false, true) => scroll.horizontal(),
+        _ => scroll,
+    }
+}
+
+impl MutableWidget for Scroll {
+    fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, mut_iter: MutationIter) {
+        if let Some(Payload::View(view)) = body {
+            if let Some(v) = view.as_any().downcast_ref::<view::Scroll>() {
+                let axes = (v.vertical, v.horizontal);
+                if axes != self.axes {
+                    // Druid's scroll can't change its axes.
+                    self.axes = axes;
+                    let content = std::mem::replace(self.inner.child_mut(), Content::new());
+                    self.inner = make_inner(content, axes);
+                    ctx.children_changed();
+                }
+                self.set_view(v);
+            }
+        }
+        self.inner.child_mut().child.mutate(ctx, mut_iter);
+        if self.pending.is_some() {
+            // Resolve the target after the new content is laid out.
+            self.laid_out = false;
+            ctx.request_layout();
+            ctx.request_anim_frame();
+        }
+    }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        let rect = self.inner.child().child.locate(id)?;
+        Some(rect - self.inner.offset())
+    }
+}
+
+impl Widget<DruidAppData> for Scroll {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        let offset = self.inner.offset();
+        self.inner.event(ctx, event, data, env);
+        if self.inner.offset() != offset {
+            // The user scrolled, which takes over from the view.
+            self.animating = None;
+            self.report(data);
+        }
+        if let Event::AnimFrame(interval) = event {
+            if self.resized {
+                self.resized = false;
+                data.queue_action(self.id, Action::Resized(ctx.size()));
+            }
+            self.animate(ctx, *interval, data);
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        match event {
+            LifeCycle::WidgetAdded if self.pending.is_some() => ctx.request_anim_frame(),
+            LifeCycle::Size(_) => {
+                // Actions can't be queued here, so report in the next frame.
+                self.resized = true;
+                ctx.request_anim_frame();
+            }
+            _ => (),
+        }
+        self.inner.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.inner.update(ctx, old_data, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        let size = self.inner.layout(ctx, bc, data, env);
+        self.laid_out = true;
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        self.inner.paint(ctx, data, env);
+    }
+}
+
+impl Content {
+    fn new() -> Self {
+        Content {
+            child: SingleChild::new(),
+        }
+    }
+}
+
+impl Widget<DruidAppData> for Content {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        if let Some(child) = self.child.get_mut() {
+            child.event(ctx, event, data, env);
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        if let Some(child) = self.child.get_mut() {
+            child.lifecycle(ctx, event, data, env);
+        }
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &DruidAppData,
+        data: &DruidAppData,
+        env: &Env,
+    ) {
+        if let Some(child) = self.child.get_mut() {
+            child.update(ctx, data, env);
+        }
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        if let Some(child) = self.child.get_mut() {
+            let size = child.layout(ctx, bc, data, env);
+            child.set_origin(ctx, data, env, Point::ZERO);
+            size
+        } else {
+            bc.min()
+        }
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &DruidAppData, env: &Env) {
+        if let Some(child) = self.child.get_mut() {
+            child.paint(ctx, data, env);
+        }
+    }
+}
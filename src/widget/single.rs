@@ -1,9 +1,11 @@
-use crate::{any_widget::AnyWidget, DruidAppData, MutIterItem};
-use druid::{EventCtx, WidgetPod};
+use crate::{any_widget::AnyWidget, DruidAppData, Id, MutIterItem};
+use druid::{EventCtx, Rect, WidgetPod};
 
 /// Helper struct for single-child widgets.
 pub struct SingleChild {
     children: Vec<WidgetPod<DruidAppData, AnyWidget>>,
+    /// The id of the view of each child.
+    ids: Vec<Id>,
 }
 
 impl SingleChild {
@@ -11,6 +13,7 @@ impl SingleChild {
     pub fn new() -> Self {
         SingleChild {
             children: Vec::new(),
+            ids: Vec::new(),
         }
     }
 
@@ -24,6 +27,13 @@ impl SingleChild {
         self.children.get_mut(0)
     }
 
+    /// Locate the view with the id in the interesting child, for
+    /// implementing `MutableWidget::locate`.
+    pub fn locate(&self, id: Id) -> Option<Rect> {
+        let child = self.children.get(0)?;
+        super::locate_in_child(child, self.ids[0], id)
+    }
+
     /// Apply mutations, potentially changing the interesting child.
     ///
     /// If the interesting child changed, it will call [`EventCtx::children_changed`].
@@ -37,6 +47,7 @@ impl SingleChild {
                 }
                 MutIterItem::Delete(n) => {
                     self.children.drain(ix..ix + n);
+                    self.ids.drain(ix..ix + n);
                     if ix == 0 {
                         children_changed = true;
                     }
@@ -44,6 +55,7 @@ impl SingleChild {
                 MutIterItem::Insert(id, body, child_iter) => {
                     let child = AnyWidget::mutate_insert(ctx, id, body, child_iter);
                     self.children.insert(ix, WidgetPod::new(child));
+                    self.ids.insert(ix, id);
                     if ix == 0 {
                         children_changed = true;
                     }
@@ -55,6 +67,30 @@ impl SingleChild {
                     });
                     ix += 1;
                 }
+                MutIterItem::Move(n, body, child_iter) => {
+                    let child = self.children.remove(ix + n);
+                    self.children.insert(ix, child);
+                    let id = self.ids.remove(ix + n);
+                    self.ids.insert(ix, id);
+                    self.children[ix].with_event_context(ctx, |child, ctx| {
+                        child.mutate_update(ctx, body, child_iter);
+                    });
+                    if ix == 0 {
+                        children_changed = true;
+                    }
+                    ix += 1;
+                }
+                MutIterItem::MoveBack(n) => {
+                    let child = self.children.remove(ix - 1 - n);
+                    let id = self.ids.remove(ix - 1 - n);
+                    ix -= 1;
+                    self.children.insert(ix, child);
+                    self.ids.insert(ix, id);
+                    // It was the first child.
+                    if ix == n {
+                        children_changed = true;
+                    }
+                }
             }
         }
         if children_changed {
@@ -1,7 +1,7 @@
 //! A widget with predefined size.
 
-use crate::{view, DruidAppData, MutableWidget, MutationIter, Payload, SingleChild};
-use druid::{widget::prelude::*, Point};
+use crate::{view, DruidAppData, Id, MutableWidget, MutationIter, Payload, SingleChild};
+use druid::{widget::prelude::*, Point, Rect};
 
 /// A widget with predefined size.
 ///
@@ -30,6 +30,10 @@ impl MutableWidget for SizedBox {
 
         self.inner.mutate(ctx, mut_iter);
     }
+
+    fn locate(&self, id: Id) -> Option<Rect> {
+        self.inner.locate(id)
+    }
 }
 
 impl SizedBox {
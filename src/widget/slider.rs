@@ -0,0 +1,101 @@
+use crate::{any_widget::Action, view, DruidAppData, Id, MutableWidget, MutationIter, Payload};
+use druid::{widget::prelude::*, WidgetPod};
+
+/// A wrapper around `druid::Slider` with `DruidAppData` instead of `f64`.
+///
+/// Druid's slider is continuous, so snapping to the step is done here, in
+/// the same way as `view::Slider::build` does for values from elsewhere.
+pub struct Slider {
+    id: Id,
+    value: f64,
+    range: (f64, f64),
+    step: Option<f64>,
+    inner: WidgetPod<f64, druid::widget::Slider>,
+}
+
+impl Slider {
+    pub fn new(id: Id, view: &view::Slider) -> Self {
+        Slider {
+            id,
+            value: view.value,
+            range: (view.min, view.max),
+            step: view.step,
+            inner: make_inner(view.min, view.max),
+        }
+    }
+
+    fn snap(&self, value: f64) -> f64 {
+        let (min, max) = self.range;
+        view::snap(value, min, max, self.step)
+    }
+}
+
+fn make_inner(min: f64, max: f64) -> WidgetPod<f64, druid::widget::Slider> {
+    WidgetPod::new(druid::widget::Slider::new().with_range(min, max))
+}
+
+impl MutableWidget for Slider {
+    fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, _mut_iter: MutationIter) {
+        if let Some(Payload::View(view)) = body {
+            if let Some(v) = view.as_any().downcast_ref::<view::Slider>() {
+                if (v.min, v.max) != self.range {
+                    // Druid's slider can't change its range.
+                    self.range = (v.min, v.max);
+                    self.inner = make_inner(v.min, v.max);
+                    ctx.children_changed();
+                }
+                self.step = v.step;
+                self.value = v.value;
+                ctx.request_update();
+            }
+        }
+    }
+}
+
+impl druid::Widget<DruidAppData> for Slider {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        let mut value = self.value;
+        self.inner.event(ctx, event, &mut value, env);
+        let value = self.snap(value);
+        if value != self.value {
+            data.queue_action(self.id, Action::ValueChanged(value));
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.inner.lifecycle(ctx, event, &self.value, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &DruidAppData,
+        _data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.inner.update(ctx, &self.value, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        let size = self.inner.layout(ctx, bc, &self.value, env);
+        self.inner
+            .set_layout_rect(ctx, &self.value, env, size.to_rect());
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &DruidAppData, env: &Env) {
+        self.inner.paint(ctx, &self.value, env);
+    }
+}
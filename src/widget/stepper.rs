@@ -0,0 +1,98 @@
+use crate::{any_widget::Action, view, DruidAppData, Id, MutableWidget, MutationIter, Payload};
+use druid::{widget::prelude::*, WidgetPod};
+
+/// A wrapper around `druid::Stepper` with `DruidAppData` instead of `f64`.
+pub struct Stepper {
+    id: Id,
+    value: f64,
+    config: (f64, f64, f64, bool),
+    inner: WidgetPod<f64, druid::widget::Stepper>,
+}
+
+impl Stepper {
+    pub fn new(id: Id, view: &view::Stepper) -> Self {
+        Stepper {
+            id,
+            value: view.value,
+            config: config(view),
+            inner: make_inner(view),
+        }
+    }
+}
+
+/// The range, step and wraparound of the view.
+fn config(view: &view::Stepper) -> (f64, f64, f64, bool) {
+    (view.min, view.max, view.step, view.wraparound)
+}
+
+fn make_inner(view: &view::Stepper) -> WidgetPod<f64, druid::widget::Stepper> {
+    let stepper = druid::widget::Stepper::new()
+        .with_range(view.min, view.max)
+        .with_step(view.step)
+        .with_wraparound(view.wraparound);
+    WidgetPod::new(stepper)
+}
+
+impl MutableWidget for Stepper {
+    fn mutate(&mut self, ctx: &mut EventCtx, body: Option<&Payload>, _mut_iter: MutationIter) {
+        if let Some(Payload::View(view)) = body {
+            if let Some(v) = view.as_any().downcast_ref::<view::Stepper>() {
+                if config(v) != self.config {
+                    // Druid's stepper can't change its configuration.
+                    self.config = config(v);
+                    self.inner = make_inner(v);
+                    ctx.children_changed();
+                }
+                self.value = v.value;
+                ctx.request_update();
+            }
+        }
+    }
+}
+
+impl druid::Widget<DruidAppData> for Stepper {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut DruidAppData, env: &Env) {
+        let mut value = self.value;
+        self.inner.event(ctx, event, &mut value, env);
+        if value != self.value {
+            data.queue_action(self.id, Action::ValueChanged(value));
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        _data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.inner.lifecycle(ctx, event, &self.value, env);
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        _old_data: &DruidAppData,
+        _data: &DruidAppData,
+        env: &Env,
+    ) {
+        self.inner.update(ctx, &self.value, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &DruidAppData,
+        env: &Env,
+    ) -> Size {
+        let size = self.inner.layout(ctx, bc, &self.value, env);
+        self.inner
+            .set_layout_rect(ctx, &self.value, env, size.to_rect());
+        size
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, _data: &DruidAppData, env: &Env) {
+        self.inner.paint(ctx, &self.value, env);
+    }
+}
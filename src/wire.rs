@@ -0,0 +1,484 @@
+//! A wire format for tree mutations and actions.
+//!
+//! These types mirror `Mutation` and `Action`, with views described as
+//! data rather than as `Box<dyn View>`, so app logic can run in one
+//! process and drive a `Renderer` in another. With the `serde` feature,
+//! they can be serialized in any format supported by serde.
+//!
+//! Only the structure of non-view elements is sent; their state stays
+//! with the app logic.
+
+use std::time::Duration;
+
+use druid::{Color, Rect, Size, UnitPoint, Vec2};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::any_widget::Action;
+use crate::id::Id;
+use crate::view::{self, ScrollTarget, View};
+
+/// A tree mutation in a form that can be sent to another process.
+///
+/// It is made with `Mutation::to_wire` and applied, on the other side,
+/// by a `Renderer`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WireMutation {
+    pub items: Vec<WireItem>,
+}
+
+/// One item of a `WireMutation`, with the meaning of the corresponding
+/// item of a `Mutation`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WireItem {
+    Skip(usize),
+    Delete(usize),
+    Insert(Vec<WireSlot>),
+    Update(Vec<WireSlot>),
+    Move { from: usize, n: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WireSlot {
+    Begin { id: Id, body: WirePayload },
+    End,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WirePayload {
+    /// An element that is not a view, such as state.
+    Transparent,
+    View(ViewData),
+}
+
+/// A description of one of the built-in views.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ViewData {
+    Label(String),
+    Button {
+        text: String,
+        disabled: bool,
+        style: ButtonStyleData,
+    },
+    Row,
+    Column,
+    TextBox(String),
+    Checkbox {
+        label: String,
+        state: bool,
+    },
+    Slider {
+        value: f64,
+        min: f64,
+        max: f64,
+        step: Option<f64>,
+    },
+    Stepper {
+        value: f64,
+        min: f64,
+        max: f64,
+        step: f64,
+        wraparound: bool,
+    },
+    Switch(bool),
+    Clicked,
+    Padding {
+        left: f64,
+        top: f64,
+        right: f64,
+        bottom: f64,
+    },
+    SizedBox {
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    Align {
+        x: f64,
+        y: f64,
+        width_factor: Option<f64>,
+        height_factor: Option<f64>,
+    },
+    Window {
+        title: String,
+        size: Option<(f64, f64)>,
+    },
+    Scroll {
+        vertical: bool,
+        horizontal: bool,
+        offset: Option<(f64, f64)>,
+        scroll_to: Option<Id>,
+        /// The duration of animated scrolling, in seconds.
+        animation: Option<f64>,
+    },
+    /// A view with no data form, such as a `Painter` or a user-defined
+    /// view, by its `Debug` output. It is rendered as an `Opaque`
+    /// placeholder.
+    Opaque(String),
+}
+
+/// The style of a button, with colors as RGBA.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ButtonStyleData {
+    pub text_color: Option<u32>,
+    pub text_size: Option<f64>,
+    pub background: Option<u32>,
+    pub border_color: Option<u32>,
+    pub border_width: Option<f64>,
+    pub border_radius: Option<f64>,
+}
+
+/// An action for the element with the id, sent back to the app logic.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WireAction {
+    pub id: Id,
+    pub action: ActionData,
+}
+
+/// The actions produced by the built-in widgets.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ActionData {
+    Clicked,
+    TextChanged(String),
+    Toggled(bool),
+    ValueChanged(f64),
+    Scrolled(f64, f64),
+    Resized(f64, f64),
+    WindowClosed,
+}
+
+impl ViewData {
+    /// Describe the view as data.
+    pub fn from_view(view: &dyn View) -> ViewData {
+        let any = view.as_any();
+        if let Some(v) = any.downcast_ref::<view::Label>() {
+            ViewData::Label(v.0.clone())
+        } else if let Some(v) = any.downcast_ref::<view::Button>() {
+            ViewData::Button {
+                text: v.text.clone(),
+                disabled: v.disabled,
+                style: ButtonStyleData::from_style(&v.style),
+            }
+        } else if any.is::<view::Row>() {
+            ViewData::Row
+        } else if any.is::<view::Column>() {
+            ViewData::Column
+        } else if let Some(v) = any.downcast_ref::<view::TextBox>() {
+            ViewData::TextBox(v.0.clone())
+        } else if let Some(v) = any.downcast_ref::<view::Checkbox>() {
+            ViewData::Checkbox {
+                label: v.label.clone(),
+                state: v.state,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Slider>() {
+            ViewData::Slider {
+                value: v.value,
+                min: v.min,
+                max: v.max,
+                step: v.step,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Stepper>() {
+            ViewData::Stepper {
+                value: v.value,
+                min: v.min,
+                max: v.max,
+                step: v.step,
+                wraparound: v.wraparound,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Switch>() {
+            ViewData::Switch(v.state)
+        } else if any.is::<view::Clicked>() {
+            ViewData::Clicked
+        } else if let Some(v) = any.downcast_ref::<view::Padding>() {
+            ViewData::Padding {
+                left: v.insets.x0,
+                top: v.insets.y0,
+                right: v.insets.x1,
+                bottom: v.insets.y1,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::SizedBox>() {
+            ViewData::SizedBox {
+                width: v.width,
+                height: v.height,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Align>() {
+            let point = v.align.resolve(Rect::new(0., 0., 1., 1.));
+            ViewData::Align {
+                x: point.x,
+                y: point.y,
+                width_factor: v.width_factor,
+                height_factor: v.height_factor,
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Window>() {
+            ViewData::Window {
+                title: v.title.clone(),
+                size: v.size.map(|size| (size.width, size.height)),
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Scroll>() {
+            let (offset, scroll_to) = match v.target {
+                Some(ScrollTarget::Offset(offset)) => (Some((offset.x, offset.y)), None),
+                Some(ScrollTarget::Node(id)) => (None, Some(id)),
+                None => (None, None),
+            };
+            ViewData::Scroll {
+                vertical: v.vertical,
+                horizontal: v.horizontal,
+                offset,
+                scroll_to,
+                animation: v.animation.map(|duration| duration.as_secs_f64()),
+            }
+        } else if let Some(v) = any.downcast_ref::<view::Opaque>() {
+            ViewData::Opaque(v.0.clone())
+        } else {
+            ViewData::Opaque(format!("{:?}", view))
+        }
+    }
+
+    /// Make a view from the description.
+    pub fn into_view(self) -> Box<dyn View> {
+        match self {
+            ViewData::Label(text) => Box::new(view::Label::new(text)),
+            ViewData::Button {
+                text,
+                disabled,
+                style,
+            } => Box::new(
+                view::Button::new(text)
+                    .disabled(disabled)
+                    .style(style.into_style()),
+            ),
+            ViewData::Row => Box::new(view::Row::new()),
+            ViewData::Column => Box::new(view::Column::new()),
+            ViewData::TextBox(content) => Box::new(view::TextBox::new(content)),
+            ViewData::Checkbox { label, state } => Box::new(view::Checkbox::new(label, state)),
+            ViewData::Slider {
+                value,
+                min,
+                max,
+                step,
+            } => Box::new(view::Slider {
+                value,
+                min,
+                max,
+                step,
+            }),
+            ViewData::Stepper {
+                value,
+                min,
+                max,
+                step,
+                wraparound,
+            } => Box::new(view::Stepper {
+                value,
+                min,
+                max,
+                step,
+                wraparound,
+            }),
+            ViewData::Switch(state) => Box::new(view::Switch::new(state)),
+            ViewData::Clicked => Box::new(view::Clicked::new()),
+            ViewData::Padding {
+                left,
+                top,
+                right,
+                bottom,
+            } => Box::new(view::Padding::from((left, top, right, bottom))),
+            ViewData::SizedBox { width, height } => Box::new(view::SizedBox { width, height }),
+            ViewData::Align {
+                x,
+                y,
+                width_factor,
+                height_factor,
+            } => Box::new(view::Align {
+                align: UnitPoint::new(x, y),
+                width_factor,
+                height_factor,
+            }),
+            ViewData::Window { title, size } => {
+                let mut window = view::Window::new(title);
+                if let Some(size) = size {
+                    window = window.size(size);
+                }
+                Box::new(window)
+            }
+            ViewData::Scroll {
+                vertical,
+                horizontal,
+                offset,
+                scroll_to,
+                animation,
+            } => {
+                let target = match (offset, scroll_to) {
+                    (Some(offset), _) => Some(ScrollTarget::Offset(offset.into())),
+                    (None, Some(id)) => Some(ScrollTarget::Node(id)),
+                    (None, None) => None,
+                };
+                Box::new(view::Scroll {
+                    vertical,
+                    horizontal,
+                    target,
+                    animation: animation.map(Duration::from_secs_f64),
+                })
+            }
+            ViewData::Opaque(text) => Box::new(view::Opaque::new(text)),
+        }
+    }
+}
+
+impl ButtonStyleData {
+    pub fn from_style(style: &view::ButtonStyle) -> ButtonStyleData {
+        let rgba = |color: &Option<Color>| color.as_ref().map(Color::as_rgba_u32);
+        ButtonStyleData {
+            text_color: rgba(&style.text_color),
+            text_size: style.text_size,
+            background: rgba(&style.background),
+            border_color: rgba(&style.border_color),
+            border_width: style.border_width,
+            border_radius: style.border_radius,
+        }
+    }
+
+    pub fn into_style(self) -> view::ButtonStyle {
+        let color = |rgba: Option<u32>| rgba.map(Color::from_rgba32_u32);
+        view::ButtonStyle {
+            text_color: color(self.text_color),
+            text_size: self.text_size,
+            background: color(self.background),
+            border_color: color(self.border_color),
+            border_width: self.border_width,
+            border_radius: self.border_radius,
+        }
+    }
+}
+
+impl ActionData {
+    /// Describe the action as data, if it can be sent.
+    ///
+    /// Only the actions of built-in widgets can be sent; user-defined
+    /// actions stay with the renderer.
+    pub fn from_action(action: &Action) -> Option<ActionData> {
+        match action {
+            Action::Clicked => Some(ActionData::Clicked),
+            Action::TextChanged(text) => Some(ActionData::TextChanged(text.clone())),
+            Action::Toggled(state) => Some(ActionData::Toggled(*state)),
+            Action::ValueChanged(value) => Some(ActionData::ValueChanged(*value)),
+            Action::Scrolled(offset) => Some(ActionData::Scrolled(offset.x, offset.y)),
+            Action::Resized(size) => Some(ActionData::Resized(size.width, size.height)),
+            Action::WindowClosed => Some(ActionData::WindowClosed),
+            Action::FutureResolved | Action::Custom(_) => None,
+        }
+    }
+
+    pub fn into_action(self) -> Action {
+        match self {
+            ActionData::Clicked => Action::Clicked,
+            ActionData::TextChanged(text) => Action::TextChanged(text),
+            ActionData::Toggled(state) => Action::Toggled(state),
+            ActionData::ValueChanged(value) => Action::ValueChanged(value),
+            ActionData::Scrolled(x, y) => Action::Scrolled(Vec2::new(x, y)),
+            ActionData::Resized(width, height) => Action::Resized(Size::new(width, height)),
+            ActionData::WindowClosed => Action::WindowClosed,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{ViewData, WireItem, WireMutation};
+    use crate::{Headless, Id, Label, List, ListData, Mutation, Opaque, Painter, Payload, Tree};
+
+    /// The id and `Debug` output of each view in the tree.
+    fn views(tree: &Tree) -> Vec<(Id, String)> {
+        tree.iter()
+            .filter_map(|(id, body)| match body {
+                Payload::View(view) => Some((id, format!("{:?}", view))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn mutations_round_trip_through_serde() {
+        let data = Rc::new(RefCell::new(ListData::default()));
+        for val in &["a", "b", "c"] {
+            data.borrow_mut().push(val.to_string());
+        }
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let app_data = data.clone();
+        let mut list = List::default();
+        let on_mutation = sent.clone();
+        let mut app = Headless::new(move |cx| {
+            list.run(cx, &app_data.borrow(), |cx, _, _, val| {
+                Label::new(val.clone()).build(cx);
+            });
+        })
+        .on_mutation(move |mutation| {
+            let json = serde_json::to_string(&mutation.to_wire()).unwrap();
+            on_mutation.borrow_mut().push(json);
+        });
+        app.run();
+        data.borrow_mut().swap(0, 2);
+        app.run();
+
+        // Apply the mutations to a tree of its own, as a renderer does.
+        let mut mirror = Tree::default();
+        let mut moves = 0;
+        for json in sent.borrow().iter() {
+            let wire: WireMutation = serde_json::from_str(json).unwrap();
+            moves += wire
+                .items
+                .iter()
+                .filter(|item| matches!(item, WireItem::Move { .. }))
+                .count();
+            let mutation = Mutation::from_wire(wire);
+            mirror.mutate(mutation);
+        }
+        assert!(moves > 0);
+        assert_eq!(views(&mirror), views(app.tree()));
+    }
+
+    #[test]
+    fn opaque_views_round_trip_as_placeholders() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let on_mutation = sent.clone();
+        let mut app = Headless::new(|cx| {
+            Painter::new(0u32).build(cx, |_, _, _| ());
+        })
+        .on_mutation(move |mutation| {
+            let json = serde_json::to_string(&mutation.to_wire()).unwrap();
+            on_mutation.borrow_mut().push(json);
+        });
+        app.run();
+        let mut mirror = Tree::default();
+        for json in sent.borrow().iter() {
+            let wire: WireMutation = serde_json::from_str(json).unwrap();
+            mirror.mutate(Mutation::from_wire(wire));
+        }
+
+        // The renderer shows a placeholder, not a label of the app's own.
+        let views: Vec<ViewData> = mirror
+            .iter()
+            .filter_map(|(_, body)| match body {
+                Payload::View(view) => {
+                    assert!(view.as_any().downcast_ref::<Label>().is_none());
+                    let opaque = view.as_any().downcast_ref::<Opaque>().unwrap();
+                    assert_eq!(opaque.description(), "Painter");
+                    Some(ViewData::from_view(view.as_ref()))
+                }
+                _ => None,
+            })
+            .collect();
+        // Described again, it is the same opaque view.
+        assert_eq!(views, vec![ViewData::Opaque("Painter".into())]);
+    }
+}